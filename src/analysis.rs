@@ -0,0 +1,141 @@
+use bevy::prelude::*;
+
+use crate::placement::Placement;
+
+/// Below this fraction of a domino's face landing on the next one, the hit may glance off.
+const RISKY_HIT_FRACTION: f32 = 0.5;
+/// Beyond this fraction of a domino's height, the next one may be just out of reach.
+const RISKY_REACH_FRACTION: f32 = 0.85;
+
+/// How likely a domino is to be knocked over by the one before it.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Risk {
+    #[default]
+    Safe,
+    /// The hit is glancing or near the end of the reach, so the chain may stop here.
+    Risky,
+    /// The domino before can't reach it, or misses it to the side.
+    Breaks,
+}
+
+/// How a domino sits against the one before it along a path.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct MarkerAnalysis {
+    /// Change of heading from the step into the domino before, in radians.
+    pub turn: f32,
+    /// Distance on the floor plane from the domino before.
+    pub spacing: f32,
+    /// How much of the falling domino's top edge lands on this one, side to side.
+    pub hit_width: f32,
+    pub risk: Risk,
+}
+
+/// Predicts, from domino sizes alone, whether each domino along a path gets knocked over.
+/// A domino falls along the step that reached it, the first one along the path,
+/// and its top edge sweeps out a strip as wide as the domino and as long as it is tall.
+pub fn analyse_path(placements: &[Placement]) -> Vec<MarkerAnalysis> {
+    let mut analyses = Vec::with_capacity(placements.len());
+    let Some(first) = placements.first() else {
+        return analyses;
+    };
+    analyses.push(MarkerAnalysis {
+        hit_width: first.kind.size().x,
+        ..default()
+    });
+    let mut fall = first.tangent.xz().normalize_or_zero();
+    for pair in placements.windows(2) {
+        let (before, after) = (&pair[0], &pair[1]);
+        let step = (after.position - before.position).xz();
+        let turn = fall.angle_to(step).abs();
+        let forward = step.dot(fall);
+        let sideways = step.perp_dot(fall).abs();
+
+        // Dominoes are the same box either way round, so only the turn off the fall line
+        // narrows the face that can be hit.
+        let half_width = before.kind.half_size().x;
+        let hit_width = half_width + after.kind.half_size().x * turn.cos().abs() - sideways;
+        let reach = before.kind.size().y;
+        let risk = if hit_width <= 0. || forward <= 0. || forward >= reach {
+            Risk::Breaks
+        } else if hit_width < half_width * RISKY_HIT_FRACTION
+            || forward > reach * RISKY_REACH_FRACTION
+        {
+            Risk::Risky
+        } else {
+            Risk::Safe
+        };
+        analyses.push(MarkerAnalysis {
+            turn,
+            spacing: step.length(),
+            hit_width: hit_width.max(0.),
+            risk,
+        });
+        fall = step.normalize_or(fall);
+    }
+    analyses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domino::{DOMINO_DISTANCE, DominoKind};
+
+    /// Standard dominoes placed one after another by the given steps on the floor,
+    /// starting from the origin along X.
+    fn placements(steps: &[Vec2]) -> Vec<Placement> {
+        let mut position = Vec3::ZERO;
+        let mut distance = 0.;
+        let mut tangent = Vec3::X;
+        let mut placements = vec![Placement {
+            distance,
+            position,
+            tangent,
+            kind: DominoKind::Standard,
+        }];
+        for step in steps {
+            position += vec3(step.x, 0., step.y);
+            distance += step.length();
+            tangent = vec3(step.x, 0., step.y).normalize();
+            placements.push(Placement {
+                distance,
+                position,
+                tangent,
+                kind: DominoKind::Standard,
+            });
+        }
+        placements
+    }
+
+    fn risks(steps: &[Vec2]) -> Vec<Risk> {
+        analyse_path(&placements(steps))
+            .iter()
+            .map(|analysis| analysis.risk)
+            .collect()
+    }
+
+    /// A straight step, turned from the X axis by `degrees`.
+    fn step(degrees: f32) -> Vec2 {
+        Vec2::from_angle(degrees.to_radians()) * DOMINO_DISTANCE
+    }
+
+    #[test]
+    fn straight_path_is_safe() {
+        assert_eq!(risks(&[step(0.); 5]), [Risk::Safe; 6]);
+    }
+
+    #[test]
+    fn sharp_turn_is_risky() {
+        let risks = risks(&[step(0.), step(0.), step(40.), step(40.)]);
+        assert_eq!(
+            risks,
+            [Risk::Safe, Risk::Safe, Risk::Safe, Risk::Risky, Risk::Safe]
+        );
+    }
+
+    #[test]
+    fn wide_gap_breaks() {
+        let gap = Vec2::X * DominoKind::Standard.size().y * 1.25;
+        let risks = risks(&[step(0.), gap, step(0.)]);
+        assert_eq!(risks, [Risk::Safe, Risk::Safe, Risk::Breaks, Risk::Safe]);
+    }
+}
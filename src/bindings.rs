@@ -0,0 +1,546 @@
+use std::collections::{BTreeMap, HashSet};
+
+use bevy::{input::InputSystem, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::game_state::GameState;
+
+pub const BINDINGS_PATH: &str = "bindings.ron";
+/// Stick deflection below which a gamepad stick counts as centred.
+const STICK_DEADZONE: f32 = 0.15;
+
+/// Everything the player can do, independent of the keys or buttons it is bound to.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    /// Adds a point, or grabs the hovered one while held.
+    PlacePoint,
+    DeletePoint,
+    Fork,
+    NewChain,
+    NextChain,
+    PreviousChain,
+    /// Cycles the kind of domino along the segment under the cursor.
+    ChangeKind,
+    /// Switches between fixed spacing and closer spacing on sharp turns.
+    ToggleSpacing,
+    /// Cycles between clicking points, freehand drawing, straight lines and arcs.
+    CycleTool,
+    /// Cycles how hard the pusher pushes.
+    CycleStrength,
+    Undo,
+    Redo,
+    Clear,
+    Start,
+    /// Stops the physics and goes back to drawing.
+    Stop,
+    NextLevel,
+    LevelSelect,
+    /// Plays back the last run, or the one saved for the level.
+    Replay,
+    /// Writes the last run to a file to share.
+    SaveReplay,
+    /// Swaps the path being drawn for the one the replay was recorded with.
+    LoadReplayPath,
+    /// Pauses the physics or a replay.
+    Pause,
+    /// Runs the physics for a single tick, pausing it.
+    Step,
+    /// Plays a replay backwards.
+    Reverse,
+    SlowDown,
+    SpeedUp,
+    /// Moves back through a replay while held.
+    ScrubBack,
+    /// Moves forward through a replay while held.
+    ScrubForward,
+    /// Opens the level editor, or plays the level being edited.
+    Editor,
+    EditWalls,
+    EditGoals,
+    EditPusher,
+    /// Places or grabs a wall, goal or the pusher while held.
+    EditorPlace,
+    RotateWallLeft,
+    RotateWallRight,
+    /// Removes the selected wall or goal.
+    EditorDelete,
+    SaveLevel,
+    /// Starts the level without waiting for its intro to finish.
+    SkipIntro,
+    /// Orbits the camera with the mouse while held.
+    Orbit,
+}
+
+impl Action {
+    /// The game states in which the action does anything.
+    pub fn states(self) -> &'static [GameState] {
+        use Action::*;
+        use GameState::*;
+        match self {
+            PlacePoint | DeletePoint | Fork | NewChain | NextChain | PreviousChain | ChangeKind
+            | ToggleSpacing | CycleTool | CycleStrength | Undo | Redo | Clear | Start => &[Drawing],
+            Stop => &[Simulating, Won, Failed, AllComplete, Replaying],
+            NextLevel => &[Won, AllComplete],
+            LevelSelect => &[Drawing, MainMenu],
+            Replay => &[Drawing, Won, Failed, AllComplete, Replaying],
+            SaveReplay => &[Drawing, Won, Failed, AllComplete],
+            LoadReplayPath | Reverse | ScrubBack | ScrubForward => &[Replaying],
+            Pause | SlowDown | SpeedUp => &[Simulating, Won, Failed, AllComplete, Replaying],
+            Step => &[Simulating, Won, Failed, AllComplete],
+            Editor => &[Drawing, Editing],
+            EditWalls | EditGoals | EditPusher | EditorPlace | RotateWallLeft | RotateWallRight
+            | EditorDelete | SaveLevel => &[Editing],
+            SkipIntro => &[LevelIntro],
+            Orbit => &[
+                Loading,
+                MainMenu,
+                LevelIntro,
+                Drawing,
+                Simulating,
+                Won,
+                Failed,
+                AllComplete,
+                Editing,
+                Replaying,
+            ],
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Binding {
+    /// A key pressed without Shift or Ctrl held.
+    Key(KeyCode),
+    /// A key pressed while either Shift is held.
+    Shift(KeyCode),
+    /// A key pressed while either Ctrl is held.
+    Ctrl(KeyCode),
+    Mouse(MouseButton),
+    Gamepad(GamepadButton),
+}
+
+impl Binding {
+    pub fn label(&self) -> String {
+        match self {
+            Binding::Key(key) => key_label(*key),
+            Binding::Shift(key) => format!("Shift+{}", key_label(*key)),
+            Binding::Ctrl(key) => format!("Ctrl+{}", key_label(*key)),
+            Binding::Mouse(MouseButton::Left) => "Left-Click".into(),
+            Binding::Mouse(MouseButton::Right) => "Right-Click".into(),
+            Binding::Mouse(MouseButton::Middle) => "Middle Mouse".into(),
+            Binding::Mouse(button) => format!("Mouse {button:?}"),
+            Binding::Gamepad(button) => gamepad_label(*button),
+        }
+    }
+
+    fn is_gamepad(&self) -> bool {
+        matches!(self, Binding::Gamepad(_))
+    }
+}
+
+/// Which bindings trigger each action. Loaded from [`BINDINGS_PATH`], which is written with
+/// the defaults if it doesn't exist, so keys can be rebound by editing the file.
+#[derive(Resource, Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Bindings {
+    pub actions: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        use Action::*;
+        use Binding::*;
+        let actions = [
+            (
+                PlacePoint,
+                vec![Mouse(MouseButton::Left), Gamepad(GamepadButton::South)],
+            ),
+            (
+                DeletePoint,
+                vec![
+                    Key(KeyCode::KeyX),
+                    Key(KeyCode::Delete),
+                    Key(KeyCode::Backspace),
+                    Gamepad(GamepadButton::West),
+                ],
+            ),
+            (
+                Fork,
+                vec![Key(KeyCode::KeyF), Gamepad(GamepadButton::North)],
+            ),
+            (
+                NewChain,
+                vec![Key(KeyCode::KeyG), Gamepad(GamepadButton::DPadUp)],
+            ),
+            (
+                NextChain,
+                vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::RightTrigger)],
+            ),
+            (
+                PreviousChain,
+                vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftTrigger)],
+            ),
+            (
+                ChangeKind,
+                vec![Key(KeyCode::KeyT), Gamepad(GamepadButton::DPadLeft)],
+            ),
+            (ToggleSpacing, vec![Key(KeyCode::KeyV)]),
+            (CycleTool, vec![Key(KeyCode::KeyB)]),
+            (CycleStrength, vec![Key(KeyCode::KeyH)]),
+            (
+                Undo,
+                vec![Key(KeyCode::KeyZ), Gamepad(GamepadButton::LeftTrigger2)],
+            ),
+            (
+                Redo,
+                vec![
+                    Key(KeyCode::KeyY),
+                    Shift(KeyCode::KeyZ),
+                    Gamepad(GamepadButton::DPadRight),
+                ],
+            ),
+            (
+                Clear,
+                vec![Key(KeyCode::KeyC), Gamepad(GamepadButton::DPadDown)],
+            ),
+            (
+                Start,
+                vec![Key(KeyCode::Space), Gamepad(GamepadButton::Start)],
+            ),
+            (Stop, vec![Key(KeyCode::KeyR), Gamepad(GamepadButton::East)]),
+            (
+                NextLevel,
+                vec![Key(KeyCode::KeyN), Gamepad(GamepadButton::South)],
+            ),
+            (
+                LevelSelect,
+                vec![Key(KeyCode::Escape), Gamepad(GamepadButton::Select)],
+            ),
+            (
+                Editor,
+                vec![Key(KeyCode::Tab), Gamepad(GamepadButton::Mode)],
+            ),
+            (
+                EditWalls,
+                vec![Key(KeyCode::Digit1), Gamepad(GamepadButton::DPadLeft)],
+            ),
+            (
+                EditGoals,
+                vec![Key(KeyCode::Digit2), Gamepad(GamepadButton::DPadUp)],
+            ),
+            (
+                EditPusher,
+                vec![Key(KeyCode::Digit3), Gamepad(GamepadButton::DPadRight)],
+            ),
+            (
+                EditorPlace,
+                vec![Mouse(MouseButton::Left), Gamepad(GamepadButton::South)],
+            ),
+            (
+                RotateWallLeft,
+                vec![Key(KeyCode::KeyQ), Gamepad(GamepadButton::LeftTrigger)],
+            ),
+            (
+                RotateWallRight,
+                vec![Key(KeyCode::KeyE), Gamepad(GamepadButton::RightTrigger)],
+            ),
+            (
+                EditorDelete,
+                vec![
+                    Key(KeyCode::Delete),
+                    Key(KeyCode::Backspace),
+                    Gamepad(GamepadButton::West),
+                ],
+            ),
+            (
+                SaveLevel,
+                vec![Ctrl(KeyCode::KeyS), Gamepad(GamepadButton::Start)],
+            ),
+            (
+                SkipIntro,
+                vec![
+                    Key(KeyCode::Enter),
+                    Key(KeyCode::Space),
+                    Mouse(MouseButton::Left),
+                    Gamepad(GamepadButton::South),
+                    Gamepad(GamepadButton::Start),
+                ],
+            ),
+            (Replay, vec![Key(KeyCode::KeyP)]),
+            (SaveReplay, vec![Shift(KeyCode::KeyP)]),
+            (LoadReplayPath, vec![Key(KeyCode::KeyL)]),
+            (Pause, vec![Key(KeyCode::Space)]),
+            (Step, vec![Key(KeyCode::Period)]),
+            (Reverse, vec![Key(KeyCode::KeyK)]),
+            (SlowDown, vec![Key(KeyCode::ArrowDown)]),
+            (SpeedUp, vec![Key(KeyCode::ArrowUp)]),
+            (ScrubBack, vec![Key(KeyCode::ArrowLeft)]),
+            (ScrubForward, vec![Key(KeyCode::ArrowRight)]),
+            (
+                Orbit,
+                vec![Mouse(MouseButton::Right), Mouse(MouseButton::Middle)],
+            ),
+        ];
+        Self {
+            actions: actions.into_iter().collect(),
+        }
+    }
+}
+
+impl Bindings {
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.actions.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Every binding for an action, with gamepad buttons in brackets, e.g. `Z [B]`.
+    pub fn describe(&self, action: Action) -> String {
+        let bindings = self.get(action);
+        let mut value = bindings
+            .iter()
+            .filter(|binding| !binding.is_gamepad())
+            .map(Binding::label)
+            .collect::<Vec<_>>()
+            .join(" or ");
+        for binding in bindings.iter().filter(|binding| binding.is_gamepad()) {
+            if !value.is_empty() {
+                value.push(' ');
+            }
+            value.push_str(&format!("[{}]", binding.label()));
+        }
+        if value.is_empty() {
+            value.push_str("(unbound)");
+        }
+        value
+    }
+
+    /// The first binding of an action, for short prompts.
+    pub fn primary(&self, action: Action) -> String {
+        self.get(action)
+            .first()
+            .map_or_else(|| "(unbound)".into(), Binding::label)
+    }
+}
+
+/// The actions triggered this frame, from every bound keyboard, mouse and gamepad input.
+#[derive(Resource, Default, Debug)]
+pub struct ActionState {
+    pressed: HashSet<Action>,
+    just_pressed: HashSet<Action>,
+    /// Left stick of the most recently used gamepad, which moves the cursor.
+    pub move_stick: Vec2,
+    /// Right stick of the most recently used gamepad, which orbits the camera.
+    pub orbit_stick: Vec2,
+}
+
+impl ActionState {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.pressed.contains(&action)
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.just_pressed.contains(&action)
+    }
+}
+
+pub struct BindingsPlugin;
+
+impl Plugin for BindingsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(read_bindings_file())
+            .insert_resource(ActionState::default())
+            .add_systems(PreUpdate, update_actions.after(InputSystem));
+    }
+}
+
+pub fn update_actions(
+    bindings: Res<Bindings>,
+    keyboard: Res<ButtonInput<KeyCode>>,
+    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    gamepads: Query<&Gamepad>,
+    mut actions: ResMut<ActionState>,
+) {
+    let shift = keyboard.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+    let ctrl = keyboard.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let is_active = |binding: &Binding, just: bool| match binding {
+        Binding::Key(key) if !shift && !ctrl => {
+            if just {
+                keyboard.just_pressed(*key)
+            } else {
+                keyboard.pressed(*key)
+            }
+        }
+        Binding::Key(_) => false,
+        Binding::Shift(key) if shift => {
+            if just {
+                keyboard.just_pressed(*key)
+            } else {
+                keyboard.pressed(*key)
+            }
+        }
+        Binding::Shift(_) => false,
+        Binding::Ctrl(key) if ctrl => {
+            if just {
+                keyboard.just_pressed(*key)
+            } else {
+                keyboard.pressed(*key)
+            }
+        }
+        Binding::Ctrl(_) => false,
+        Binding::Mouse(button) => {
+            if just {
+                mouse_button_input.just_pressed(*button)
+            } else {
+                mouse_button_input.pressed(*button)
+            }
+        }
+        Binding::Gamepad(button) => gamepads.iter().any(|gamepad| {
+            if just {
+                gamepad.just_pressed(*button)
+            } else {
+                gamepad.pressed(*button)
+            }
+        }),
+    };
+
+    let ActionState {
+        pressed,
+        just_pressed,
+        ..
+    } = &mut *actions;
+    pressed.clear();
+    just_pressed.clear();
+    for (action, action_bindings) in &bindings.actions {
+        if action_bindings
+            .iter()
+            .any(|binding| is_active(binding, false))
+        {
+            pressed.insert(*action);
+        }
+        if action_bindings
+            .iter()
+            .any(|binding| is_active(binding, true))
+        {
+            just_pressed.insert(*action);
+        }
+    }
+
+    let stick = |value: Vec2| {
+        if value.length() < STICK_DEADZONE {
+            Vec2::ZERO
+        } else {
+            value
+        }
+    };
+    actions.move_stick = gamepads
+        .iter()
+        .map(|gamepad| stick(gamepad.left_stick()))
+        .find(|value| *value != Vec2::ZERO)
+        .unwrap_or_default();
+    actions.orbit_stick = gamepads
+        .iter()
+        .map(|gamepad| stick(gamepad.right_stick()))
+        .find(|value| *value != Vec2::ZERO)
+        .unwrap_or_default();
+}
+
+fn key_label(key: KeyCode) -> String {
+    match key {
+        KeyCode::Escape => "Esc".into(),
+        KeyCode::ShiftLeft | KeyCode::ShiftRight => "Shift".into(),
+        KeyCode::ControlLeft | KeyCode::ControlRight => "Ctrl".into(),
+        _ => {
+            let name = format!("{key:?}");
+            name.strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Digit"))
+                .unwrap_or(&name)
+                .into()
+        }
+    }
+}
+
+fn gamepad_label(button: GamepadButton) -> String {
+    match button {
+        GamepadButton::South => "A".into(),
+        GamepadButton::East => "B".into(),
+        GamepadButton::West => "X".into(),
+        GamepadButton::North => "Y".into(),
+        GamepadButton::LeftTrigger => "LB".into(),
+        GamepadButton::RightTrigger => "RB".into(),
+        GamepadButton::LeftTrigger2 => "LT".into(),
+        GamepadButton::RightTrigger2 => "RT".into(),
+        GamepadButton::DPadUp => "D-Pad Up".into(),
+        GamepadButton::DPadDown => "D-Pad Down".into(),
+        GamepadButton::DPadLeft => "D-Pad Left".into(),
+        GamepadButton::DPadRight => "D-Pad Right".into(),
+        GamepadButton::Mode => "Home".into(),
+        button => format!("{button:?}"),
+    }
+}
+
+/// Bindings from the config file, falling back to the defaults for any action it leaves out.
+#[cfg(not(target_arch = "wasm32"))]
+fn read_bindings_file() -> Bindings {
+    let mut bindings = Bindings::default();
+    let text = match std::fs::read_to_string(BINDINGS_PATH) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            // The file is left alone once it exists, so edits and comments in it are kept.
+            write_bindings_file(&bindings);
+            return bindings;
+        }
+        Err(err) => {
+            error!("Could not read {BINDINGS_PATH}: {err}");
+            return bindings;
+        }
+    };
+    match ron::from_str::<Bindings>(&text) {
+        Ok(file) => bindings.actions.extend(file.actions),
+        Err(err) => error!("Could not parse {BINDINGS_PATH}, using the defaults: {err}"),
+    }
+    bindings
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_bindings_file(bindings: &Bindings) {
+    let text = match ron::ser::to_string_pretty(bindings, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("Could not serialize bindings: {err}");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::write(BINDINGS_PATH, text) {
+        error!("Could not write {BINDINGS_PATH}: {err}");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_bindings_file() -> Bindings {
+    Bindings::default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_bindings_dont_overlap_within_a_state() {
+        let bindings = Bindings::default();
+        for (first, first_bindings) in &bindings.actions {
+            for (second, second_bindings) in bindings.actions.range(first..).skip(1) {
+                let shares_state = first
+                    .states()
+                    .iter()
+                    .any(|state| second.states().contains(state));
+                if !shares_state {
+                    continue;
+                }
+                for binding in first_bindings {
+                    assert!(
+                        !second_bindings.contains(binding),
+                        "{first:?} and {second:?} are both bound to {binding:?}"
+                    );
+                }
+            }
+        }
+    }
+}
@@ -3,10 +3,11 @@ use std::{f32::consts::FRAC_PI_2, ops::Range};
 
 use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
 
-const CAMERA_DISTANCE: f32 = 30.;
+use crate::bindings::{Action, ActionState};
 
-#[derive(Component, Default)]
-pub struct CameraPivot;
+const CAMERA_DISTANCE: f32 = 30.;
+/// How far a fully deflected stick orbits per second, in the same units as mouse motion.
+const STICK_ORBIT_SPEED: f32 = 300.;
 
 #[derive(Debug, Resource)]
 struct CameraSettings {
@@ -62,17 +63,27 @@ fn setup_camera_rig(mut commands: Commands) {
 
 fn handle_camera_movement(
     mut camera_pivot: Single<&mut Transform, With<Camera>>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
     camera_settings: Res<CameraSettings>,
     mouse_motion: Res<AccumulatedMouseMotion>,
+    // Real time, so the camera still moves while the physics is paused or slowed.
+    time: Res<Time<Real>>,
 ) {
-    if mouse_button_input.pressed(MouseButton::Right)
-        || mouse_button_input.pressed(MouseButton::Middle)
-    {
-        let delta = mouse_motion.delta;
+    let is_orbiting = actions.pressed(Action::Orbit);
+    if is_orbiting || actions.orbit_stick != Vec2::ZERO {
         // Mouse motion is one of the few inputs that should not be multiplied by delta time,
         // as we are already receiving the full movement since the last frame was rendered. Multiplying
         // by delta time here would make the movement slower that it should be.
+        let mut delta = if is_orbiting {
+            mouse_motion.delta
+        } else {
+            Vec2::ZERO
+        };
+        // Stick deflection is a rate, so it does need delta time. Up on the stick matches moving
+        // the mouse up.
+        delta += vec2(actions.orbit_stick.x, -actions.orbit_stick.y)
+            * STICK_ORBIT_SPEED
+            * time.delta_secs();
         let delta_pitch = delta.y * camera_settings.pitch_speed;
         let delta_yaw = delta.x * camera_settings.yaw_speed;
 
@@ -1,18 +1,75 @@
+use crate::bindings::ActionState;
+use crate::environment::FLOOR_HALF_SIZE;
 use crate::floor::Floor;
-use bevy::prelude::*;
+use crate::level::LevelLayout;
+use crate::terrain::Terrain;
+use bevy::{input::mouse::AccumulatedMouseMotion, prelude::*};
+use bevy_rapier3d::prelude::{QueryFilter, ReadRapierContext};
 // use bevy_simple_subsecond_system::hot;
 
-#[derive(Resource, Default)]
+/// How fast a fully deflected gamepad stick moves the cursor across the floor, per second.
+const STICK_CURSOR_SPEED: f32 = 15.;
+/// Furthest the cursor's ray looks for ground.
+const MAX_RAY_DISTANCE: f32 = 1000.;
+
+/// Anything the cursor can rest on.
+type Ground = Or<(With<Floor>, With<Terrain>)>;
+
+/// Where the cursor points on the ground.
+#[derive(Resource)]
 pub struct Cursor {
     pub position: Vec3,
+    /// Normal of the ground under the cursor.
+    pub normal: Vec3,
+    /// Moved by a gamepad stick rather than following the mouse, until the mouse moves again.
+    pub is_virtual: bool,
+}
+
+impl Default for Cursor {
+    fn default() -> Self {
+        Self {
+            position: Vec3::ZERO,
+            normal: Vec3::Y,
+            is_virtual: false,
+        }
+    }
 }
+
 pub struct CursorPlugin;
 
 impl Plugin for CursorPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Cursor::default())
-            .add_systems(Update, draw_cursor);
+            .add_systems(Update, (move_virtual_cursor, draw_cursor).chain());
+    }
+}
+
+/// Moves the cursor across the floor with the gamepad stick, relative to the camera's view.
+fn move_virtual_cursor(
+    actions: Res<ActionState>,
+    mouse_motion: Res<AccumulatedMouseMotion>,
+    camera_transform: Single<&GlobalTransform, With<Camera>>,
+    layout: Res<LevelLayout>,
+    time: Res<Time<Real>>,
+    mut cursor: ResMut<Cursor>,
+) {
+    if mouse_motion.delta != Vec2::ZERO {
+        cursor.is_virtual = false;
     }
+    if actions.move_stick == Vec2::ZERO {
+        return;
+    }
+    cursor.is_virtual = true;
+    let forward = camera_transform.forward().with_y(0.).normalize_or_zero();
+    let right = camera_transform.right().with_y(0.).normalize_or_zero();
+    let step = (right * actions.move_stick.x + forward * actions.move_stick.y)
+        * STICK_CURSOR_SPEED
+        * time.delta_secs();
+    let position =
+        (cursor.position.xz() + step.xz()).clamp(-FLOOR_HALF_SIZE.xz(), FLOOR_HALF_SIZE.xz());
+    let surface = layout.surface_at(position);
+    cursor.position = vec3(position.x, surface.height, position.y);
+    cursor.normal = surface.normal;
 }
 
 // #[hot]
@@ -20,40 +77,70 @@ fn draw_cursor(
     camera_query: Single<(&Camera, &GlobalTransform)>,
     floor: Single<&GlobalTransform, With<Floor>>,
     windows: Query<&Window>,
+    rapier_context: ReadRapierContext,
+    ground_query: Query<(), Ground>,
     mut gizmos: Gizmos,
     mut cursor: ResMut<Cursor>,
 ) {
-    let Ok(windows) = windows.single() else {
-        return;
-    };
-
-    let (camera, camera_transform) = *camera_query;
-
-    let Some(cursor_position) = windows.cursor_position() else {
-        return;
-    };
-
-    // Calculate a ray pointing from the camera into the world based on the cursor's position.
-    let Ok(ray) = camera.viewport_to_world(camera_transform, cursor_position) else {
-        return;
-    };
-
-    // Calculate if and where the ray is hitting the floor plane.
-    let Some(distance) = ray.intersect_plane(
-        floor.translation() + vec3(0.0, 1.0, 0.0),
-        InfinitePlane3d::new(floor.up()),
-    ) else {
-        return;
-    };
-    cursor.position = ray.get_point(distance);
+    if !cursor.is_virtual
+        && let Some(ray) = mouse_ray(&camera_query, &windows)
+        && let Some((position, normal)) =
+            ground_hit(ray, &rapier_context, &ground_query).or_else(|| floor_hit(ray, &floor))
+    {
+        cursor.position = position;
+        cursor.normal = normal;
+    }
 
-    // Draw a circle just above the floor plane at that position.
+    // Draw a circle just above the ground at that position.
     gizmos.circle(
         Isometry3d::new(
-            cursor.position + floor.up() * 0.01,
-            Quat::from_rotation_arc(Vec3::Z, floor.up().as_vec3()),
+            cursor.position + cursor.normal * 0.01,
+            Quat::from_rotation_arc(Vec3::Z, cursor.normal),
         ),
         0.75,
         Color::WHITE,
     );
 }
+
+/// The ray from the camera through the mouse, if it is over the window.
+fn mouse_ray(
+    camera_query: &(&Camera, &GlobalTransform),
+    windows: &Query<&Window>,
+) -> Option<Ray3d> {
+    let window = windows.single().ok()?;
+    let (camera, camera_transform) = *camera_query;
+    let cursor_position = window.cursor_position()?;
+
+    // Calculate a ray pointing from the camera into the world based on the cursor's position.
+    camera
+        .viewport_to_world(camera_transform, cursor_position)
+        .ok()
+}
+
+/// Where and at what angle the ray first hits the floor or raised terrain.
+fn ground_hit(
+    ray: Ray3d,
+    rapier_context: &ReadRapierContext,
+    ground_query: &Query<(), Ground>,
+) -> Option<(Vec3, Vec3)> {
+    let context = rapier_context.single().ok()?;
+    let predicate = |entity| ground_query.contains(entity);
+    let filter = QueryFilter::default().predicate(&predicate);
+    let (_, hit) = context.cast_ray_and_get_normal(
+        ray.origin,
+        ray.direction.as_vec3(),
+        MAX_RAY_DISTANCE,
+        true,
+        filter,
+    )?;
+    Some((hit.point, hit.normal))
+}
+
+/// Where the ray crosses the plane of the floor's top, for pointing past its edges.
+fn floor_hit(ray: Ray3d, floor: &GlobalTransform) -> Option<(Vec3, Vec3)> {
+    let distance = ray.intersect_plane(
+        floor.translation() + floor.up() * FLOOR_HALF_SIZE.y,
+        InfinitePlane3d::new(floor.up()),
+    )?;
+    Some((ray.get_point(distance), floor.up().as_vec3()))
+}
@@ -1,52 +1,186 @@
+use crate::analysis;
+use crate::bindings::{Action, ActionState};
 use crate::cursor::Cursor;
-use crate::domino::{
-    self, DOMINO_DISTANCE, DOMINO_Y_POS, Domino, DominoMarker, DominoSensor, IsAllValid,
-    VALID_COLOR,
-};
-use crate::environment::PUSHER_START_POS;
-use crate::pusher::Pusher;
+use crate::curve_history::{CurveEdit, CurveHistory};
+use crate::domino::{self, Domino, DominoKind, DominoMarker, IsAllValid};
+use crate::game_state::GameState;
+use crate::level::LevelLayout;
+use crate::level_asset::LevelDefinition;
+use crate::path_tool::{self, PathTool, SegmentShape};
+use crate::placement::{self, ArcLengthCurve, MarkerSpacing, Placement};
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 // use bevy_simple_subsecond_system::hot;
 
-pub const CONTROL_START_POS: Vec3 = vec3(PUSHER_START_POS.x, DOMINO_Y_POS, PUSHER_START_POS.z);
+/// How close the cursor must be to a control point or the curve to grab it.
+pub const GRAB_RADIUS: f32 = 0.75;
+const POINT_RADIUS: f32 = 0.3;
+const POINT_COLOR: Color = Color::WHITE;
+const INACTIVE_POINT_COLOR: Color = Color::srgb(0.5, 0.5, 0.5);
+const HOVERED_POINT_COLOR: Color = Color::srgb(1., 1., 0.);
+const FORK_COLOR: Color = Color::srgb(1., 0.5, 0.);
+const STROKE_COLOR: Color = Color::srgb(0.6, 0.8, 1.);
 
-#[derive(Default, PartialEq, Debug)]
-pub enum SimulationState {
-    #[default]
-    Draw,
-    Physics,
+/// One spline per chain, in the same order as [`ControlPoints::chains`].
+#[derive(Resource, Clone, Default)]
+struct Curves(Vec<Option<CubicCurve<Vec3>>>);
+
+/// A single line of dominoes with its own spline.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Chain {
+    pub name: String,
+    pub points: Vec<Vec3>,
+    /// The chain this one splits off from, if it starts at a fork.
+    pub parent: Option<usize>,
+    /// The kind of domino along each segment, from point `i` to point `i + 1`.
+    /// Segments past the end of the list are standard.
+    #[serde(default)]
+    pub kinds: Vec<DominoKind>,
+    /// The shape of each segment, in the same way as `kinds`. Segments past the end are smooth.
+    #[serde(default)]
+    pub shapes: Vec<SegmentShape>,
 }
 
-#[derive(Resource, Default, Debug)]
-pub struct CurrentSimulation {
-    pub state: SimulationState,
+impl Chain {
+    pub fn kind(&self, segment: usize) -> DominoKind {
+        self.kinds.get(segment).copied().unwrap_or_default()
+    }
+
+    pub fn set_kind(&mut self, segment: usize, kind: DominoKind) {
+        if self.kinds.len() <= segment {
+            self.kinds.resize(segment + 1, DominoKind::Standard);
+        }
+        self.kinds[segment] = kind;
+    }
+
+    /// Adds a point to the end, joined to the last one by a segment of the given shape.
+    pub fn push_point(&mut self, position: Vec3, shape: SegmentShape) {
+        self.points.push(position);
+        let segment = self.points.len().saturating_sub(2);
+        if shape != SegmentShape::Smooth || segment < self.shapes.len() {
+            if self.shapes.len() <= segment {
+                self.shapes.resize(segment + 1, SegmentShape::Smooth);
+            }
+            self.shapes[segment] = shape;
+        }
+    }
+
+    /// Inserts a point, splitting the segment it lands on into two of the same kind and shape.
+    pub fn insert_point(&mut self, index: usize, position: Vec3) {
+        self.points.insert(index, position);
+        split_segment(&mut self.kinds, index);
+        split_segment(&mut self.shapes, index);
+    }
+
+    /// Removes a point, merging the segments either side of it into the one before.
+    pub fn remove_point(&mut self, index: usize) {
+        self.points.remove(index);
+        let segment = index.min(self.points.len().saturating_sub(1));
+        if segment < self.kinds.len() {
+            self.kinds.remove(segment);
+        }
+        if segment < self.shapes.len() {
+            self.shapes.remove(segment);
+        }
+    }
 }
 
-#[derive(Component, Resource, Clone, Default)]
-struct Curve(Option<CubicCurve<Vec3>>);
+/// Duplicates the per-segment value before a newly inserted point `index`.
+fn split_segment<T: Copy>(segments: &mut Vec<T>, index: usize) {
+    if index > 0 && index - 1 < segments.len() {
+        segments.insert(index, segments[index - 1]);
+    }
+}
 
 #[derive(Resource, Clone, Default)]
 pub struct ControlPoints {
-    pub points: Vec<Vec3>,
+    /// The first chain starts at the pusher; the rest are forks or independent chains.
+    pub chains: Vec<Chain>,
+    /// The chain new points are appended to.
+    pub active: usize,
+    /// How markers are spaced along every chain.
+    pub spacing: MarkerSpacing,
+}
+
+impl ControlPoints {
+    pub fn reset(&mut self, start: Vec3) {
+        self.chains = vec![Chain {
+            name: "Main".into(),
+            points: vec![start],
+            parent: None,
+            kinds: Vec::new(),
+            shapes: Vec::new(),
+        }];
+        self.active = 0;
+    }
+
+    /// The points of the chain started by the pusher.
+    pub fn main_points(&self) -> &[Vec3] {
+        self.chains.first().map_or(&[], |chain| &chain.points)
+    }
+
+    pub fn point(&self, id: PointId) -> Option<Vec3> {
+        self.chains.get(id.chain)?.points.get(id.index).copied()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PointId {
+    pub chain: usize,
+    pub index: usize,
+}
+
+/// The control point under the cursor, and the one being dragged if any.
+#[derive(Resource, Default, Debug)]
+struct PointDrag {
+    hovered: Option<PointId>,
+    dragging: Option<DraggedPoint>,
+    /// The freehand stroke being drawn, sampled along the way.
+    stroke: Option<Vec<Vec3>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct DraggedPoint {
+    id: PointId,
+    from: Vec3,
 }
 
 pub struct CurvePlugin;
 
 impl Plugin for CurvePlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(Curve::default())
-            .insert_resource(CurrentSimulation::default())
+        app.insert_resource(Curves::default())
             .insert_resource(ControlPoints::default())
-            .add_systems(PostStartup, setup_curve)
-            .add_systems(FixedUpdate, animate_bump)
+            .insert_resource(CurveHistory::default())
+            .insert_resource(PointDrag::default())
+            .insert_resource(PathTool::default())
+            .add_systems(OnEnter(GameState::Drawing), clear_dominoes)
             .add_systems(
                 Update,
                 (
-                    handle_click,
-                    handle_undo,
-                    handle_start_sim,
-                    update_curve,
+                    setup_curve,
+                    (
+                        (
+                            hover_point,
+                            grab_point,
+                            handle_click,
+                            extend_stroke,
+                            drag_point,
+                            delete_point,
+                            handle_chain_keys,
+                            change_kind,
+                            toggle_spacing,
+                            cycle_tool,
+                        )
+                            .chain(),
+                        handle_undo,
+                        handle_start_sim,
+                        draw_control_points,
+                    )
+                        .run_if(in_state(GameState::Drawing)),
+                    // Replays pose their own dominoes.
+                    update_curve.run_if(not(in_state(GameState::Replaying))),
                     // draw_curve,
                 ),
             );
@@ -54,9 +188,27 @@ impl Plugin for CurvePlugin {
 }
 
 // #[hot]
-fn setup_curve(mut control_points: ResMut<ControlPoints>) {
-    control_points.points.clear();
-    control_points.points.push(CONTROL_START_POS);
+pub fn setup_curve(
+    layout: Res<LevelLayout>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    // Edits made on another layout can't be undone here.
+    history.clear();
+    control_points.reset(layout.control_start());
+}
+
+/// Takes the fallen dominoes away and puts the markers back for the same path.
+fn clear_dominoes(
+    mut commands: Commands,
+    mut control_points: ResMut<ControlPoints>,
+    query: Query<Entity, With<Domino>>,
+) {
+    despawn_entities(&mut commands, query);
+    control_points.set_changed();
 }
 
 // #[hot]
@@ -65,21 +217,29 @@ fn update_curve(
     meshes: ResMut<Assets<Mesh>>,
     materials: ResMut<Assets<StandardMaterial>>,
     control_points: ResMut<ControlPoints>,
-    mut curve: ResMut<Curve>,
+    mut curves: ResMut<Curves>,
+    layout: Res<LevelLayout>,
     query: Query<Entity, With<DominoMarker>>,
 ) {
     if !control_points.is_changed() {
         return;
     }
 
-    *curve = form_curve(&control_points);
+    *curves = Curves(control_points.chains.iter().map(form_curve).collect());
     despawn_entities(&mut commands, query);
-    spawn_markers(&mut commands, meshes, materials, curve.into());
+    spawn_markers(
+        &mut commands,
+        meshes,
+        materials,
+        curves.into(),
+        control_points.into(),
+        &layout,
+    );
 }
 
 // #[hot]
-// fn draw_curve(curve: Res<Curve>, mut gizmos: Gizmos) {
-//     let Some(ref curve) = curve.0 else {
+// fn draw_curve(curves: Res<Curves>, mut gizmos: Gizmos) {
+//     let Some(Some(curve)) = curves.0.first() else {
 //         return;
 //     };
 //     // Scale resolution with curve length so it doesn't degrade as the length increases.
@@ -92,130 +252,382 @@ fn update_curve(
 // }
 
 // #[hot]
-fn form_curve(control_points: &ControlPoints) -> Curve {
-    let points: Vec<Vec3> = control_points.points.iter().copied().collect();
-    let spline = CubicCardinalSpline::new_catmull_rom(points);
-    Curve(spline.to_curve().ok())
+pub fn form_curve(chain: &Chain) -> Option<CubicCurve<Vec3>> {
+    path_tool::build_curve(&chain.points, &chain.shapes)
+}
+
+/// Total length of every chain's spline, measured along the curves.
+pub fn path_length(control_points: &ControlPoints) -> f32 {
+    control_points
+        .chains
+        .iter()
+        .filter_map(form_curve)
+        .map(|curve| ArcLengthCurve::new(&curve).length())
+        .sum()
+}
+
+// #[hot]
+fn hover_point(
+    cursor: Res<Cursor>,
+    control_points: Res<ControlPoints>,
+    mut drag: ResMut<PointDrag>,
+) {
+    if let Some(dragged) = drag.dragging {
+        drag.hovered = Some(dragged.id);
+        return;
+    }
+    // The first point of each chain is its anchor, at the pusher or a fork, and stays put.
+    drag.hovered = control_points
+        .chains
+        .iter()
+        .enumerate()
+        .flat_map(|(chain, c)| {
+            c.points
+                .iter()
+                .enumerate()
+                .skip(1)
+                .map(move |(index, point)| (PointId { chain, index }, *point))
+        })
+        .map(|(id, point)| (id, point.xz().distance(cursor.position.xz())))
+        .filter(|(_, distance)| *distance < GRAB_RADIUS)
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+        .map(|(id, _)| id);
+}
+
+fn grab_point(
+    actions: Res<ActionState>,
+    control_points: Res<ControlPoints>,
+    mut drag: ResMut<PointDrag>,
+) {
+    if !actions.just_pressed(Action::PlacePoint) {
+        return;
+    }
+    if let Some(id) = drag.hovered
+        && let Some(from) = control_points.point(id)
+    {
+        drag.dragging = Some(DraggedPoint { id, from });
+    }
 }
 
 // #[hot]
 fn handle_click(
-    mut commands: Commands,
-    mut sim: ResMut<CurrentSimulation>,
-    mouse_button_input: Res<ButtonInput<MouseButton>>,
+    actions: Res<ActionState>,
     cursor: Res<Cursor>,
+    curves: Res<Curves>,
+    tool: Res<PathTool>,
+    mut drag: ResMut<PointDrag>,
     mut control_points: ResMut<ControlPoints>,
-    query: Query<Entity, With<Domino>>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if drag.dragging.is_some() || !actions.just_pressed(Action::PlacePoint) {
+        return;
+    }
+    let mut position = cursor.position;
+    position.y = domino::standing_y(cursor.position.y);
+    let edit = match insert_index(&curves, position) {
+        Some(PointId { chain, index }) => CurveEdit::Insert {
+            chain,
+            index,
+            position,
+        },
+        // Away from the curves, a freehand click starts a stroke instead.
+        None if *tool == PathTool::Freehand => {
+            drag.stroke = Some(vec![position]);
+            return;
+        }
+        None => CurveEdit::Add {
+            chain: control_points.active,
+            position,
+            shape: tool.shape(),
+            shape_count: control_points.chains[control_points.active].shapes.len(),
+        },
+    };
+    history.apply(edit, &mut control_points);
+}
+
+/// Samples the freehand stroke while held, then adds it to the active chain as a few points.
+fn extend_stroke(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    mut drag: ResMut<PointDrag>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
 ) {
-    if mouse_button_input.just_pressed(MouseButton::Left) {
-        sim.state = SimulationState::Draw;
-        despawn_entities(&mut commands, query);
-        let mut pos = cursor.position;
-        pos.y = domino::DOMINO_Y_POS;
-        if control_points.points.len() > 0 && control_points.points[0] == Vec3::ZERO {
-            control_points.points[0] = pos;
-        } else {
-            control_points.points.push(pos);
+    let Some(stroke) = &mut drag.stroke else {
+        return;
+    };
+    let mut position = cursor.position;
+    position.y = domino::standing_y(cursor.position.y);
+    if actions.pressed(Action::PlacePoint) {
+        if stroke
+            .last()
+            .is_none_or(|last| last.distance(position) >= path_tool::STROKE_SAMPLE_DISTANCE)
+        {
+            stroke.push(position);
         }
+        return;
     }
+    let positions = path_tool::simplify_stroke(stroke);
+    drag.stroke = None;
+    let chain = control_points.active;
+    let shape_count = control_points.chains[chain].shapes.len();
+    history.apply(
+        CurveEdit::AddStroke {
+            chain,
+            positions,
+            shape_count,
+        },
+        &mut control_points,
+    );
 }
 
-// #[hot]
-fn handle_undo(
-    mut commands: Commands,
-    mut sim: ResMut<CurrentSimulation>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+fn drag_point(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    mut drag: ResMut<PointDrag>,
     mut control_points: ResMut<ControlPoints>,
-    query: Query<Entity, With<Domino>>,
+    mut history: ResMut<CurveHistory>,
 ) {
-    if keyboard.just_pressed(KeyCode::KeyR) || keyboard.just_pressed(KeyCode::KeyZ) {
-        despawn_entities(&mut commands, query);
-        control_points.points.pop();
-        if control_points.points.is_empty() {
-            control_points.points.push(CONTROL_START_POS);
+    let Some(dragged) = drag.dragging else {
+        return;
+    };
+    // The point may have been undone from under the cursor.
+    let Some(current) = control_points.point(dragged.id) else {
+        drag.dragging = None;
+        return;
+    };
+    let PointId { chain, index } = dragged.id;
+    let mut position = cursor.position;
+    position.y = domino::standing_y(cursor.position.y);
+    if current != position {
+        control_points.chains[chain].points[index] = position;
+    }
+
+    if !actions.pressed(Action::PlacePoint) {
+        // The whole drag is a single edit, already applied while moving.
+        if dragged.from != position {
+            history.record(CurveEdit::Move {
+                chain,
+                index,
+                from: dragged.from,
+                to: position,
+            });
         }
-        sim.state = SimulationState::Draw;
+        drag.dragging = None;
     }
-    if keyboard.just_pressed(KeyCode::KeyC) {
-        despawn_entities(&mut commands, query);
-        clear_curve(control_points, sim);
+}
+
+fn delete_point(
+    actions: Res<ActionState>,
+    mut drag: ResMut<PointDrag>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if drag.dragging.is_some() {
+        return;
+    }
+    let Some(id) = drag.hovered else {
+        return;
+    };
+    if actions.just_pressed(Action::DeletePoint) {
+        let Some(position) = control_points.point(id) else {
+            return;
+        };
+        let edit = CurveEdit::Delete {
+            chain: id.chain,
+            index: id.index,
+            position,
+            kinds: control_points.chains[id.chain].kinds.clone(),
+            shapes: control_points.chains[id.chain].shapes.clone(),
+        };
+        history.apply(edit, &mut control_points);
+        drag.hovered = None;
     }
 }
 
-pub fn clear_curve(mut control_points: ResMut<ControlPoints>, mut sim: ResMut<CurrentSimulation>) {
-    control_points.points.clear();
-    control_points.points.push(CONTROL_START_POS);
-    sim.state = SimulationState::Draw;
+/// Forks a new chain off the hovered point, starts an independent chain at the cursor,
+/// or cycles which chain new points are added to.
+fn handle_chain_keys(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    drag: Res<PointDrag>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if drag.dragging.is_some() {
+        return;
+    }
+    let count = control_points.chains.len();
+    if actions.just_pressed(Action::Fork) {
+        let Some(id) = drag.hovered else {
+            return;
+        };
+        let Some(position) = control_points.point(id) else {
+            return;
+        };
+        let chain = Chain {
+            name: format!("Fork {count}"),
+            points: vec![position],
+            parent: Some(id.chain),
+            kinds: Vec::new(),
+            shapes: Vec::new(),
+        };
+        history.apply(CurveEdit::AddChain { chain }, &mut control_points);
+    } else if actions.just_pressed(Action::NewChain) {
+        let mut position = cursor.position;
+        position.y = domino::standing_y(cursor.position.y);
+        let chain = Chain {
+            name: format!("Chain {}", count + 1),
+            points: vec![position],
+            parent: None,
+            kinds: Vec::new(),
+            shapes: Vec::new(),
+        };
+        history.apply(CurveEdit::AddChain { chain }, &mut control_points);
+    } else if actions.just_pressed(Action::NextChain) {
+        control_points.active = (control_points.active + 1) % count;
+    } else if actions.just_pressed(Action::PreviousChain) {
+        control_points.active = (control_points.active + count - 1) % count;
+    }
 }
 
-// #[hot]
-fn handle_start_sim(
-    mut commands: Commands,
-    mut sim: ResMut<CurrentSimulation>,
-    keyboard: Res<ButtonInput<KeyCode>>,
-    mut query: Query<(Entity, &Transform), With<DominoMarker>>,
-    mut meshes: ResMut<Assets<Mesh>>,
-    mut materials: ResMut<Assets<StandardMaterial>>,
-    is_all_valid: Res<IsAllValid>,
+/// Cycles the kind of domino along the segment under the cursor.
+fn change_kind(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    curves: Res<Curves>,
+    drag: Res<PointDrag>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
 ) {
-    if keyboard.just_pressed(KeyCode::Space) && is_all_valid.value {
-        for (marker, transform) in query.iter_mut() {
-            let pos = transform.translation;
-            let rot = transform.rotation;
-            commands.entity(marker).despawn();
-            commands.spawn((
-                Domino,
-                RigidBody::Dynamic,
-                Collider::cuboid(
-                    domino::DOMINO_HALF_SIZE.x,
-                    domino::DOMINO_HALF_SIZE.y,
-                    domino::DOMINO_HALF_SIZE.z,
-                ),
-                Mesh3d(meshes.add(Cuboid::from_size(domino::DOMINO_SIZE))),
-                MeshMaterial3d(materials.add(Color::WHITE)),
-                Transform::from_translation(pos).with_rotation(rot),
-                Children::spawn(Spawn((
-                    Collider::cuboid(
-                        domino::DOMINO_HALF_SIZE.x,
-                        domino::DOMINO_HALF_SIZE.y,
-                        domino::DOMINO_HALF_SIZE.z,
-                    ),
-                    Sensor,
-                    DominoSensor,
-                    ActiveCollisionTypes::all(),
-                    ActiveEvents::COLLISION_EVENTS,
-                ))),
-            ));
+    if drag.dragging.is_some() || !actions.just_pressed(Action::ChangeKind) {
+        return;
+    }
+    let Some(PointId { chain, index }) = insert_index(&curves, cursor.position) else {
+        return;
+    };
+    let segment = index - 1;
+    let from = control_points.chains[chain].kind(segment);
+    let edit = CurveEdit::SetKind {
+        chain,
+        segment,
+        from,
+        to: from.next(),
+    };
+    history.apply(edit, &mut control_points);
+}
+
+fn toggle_spacing(
+    actions: Res<ActionState>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if actions.just_pressed(Action::ToggleSpacing) {
+        let from = control_points.spacing;
+        let edit = CurveEdit::SetSpacing {
+            from,
+            to: from.toggled(),
+        };
+        history.apply(edit, &mut control_points);
+    }
+}
+
+fn cycle_tool(actions: Res<ActionState>, drag: Res<PointDrag>, mut tool: ResMut<PathTool>) {
+    if drag.stroke.is_none() && actions.just_pressed(Action::CycleTool) {
+        *tool = tool.next();
+    }
+}
+
+/// Where a point placed at `position` should be inserted, if it lies on one of the curves
+/// rather than past the end of the active chain.
+fn insert_index(curves: &Curves, position: Vec3) -> Option<PointId> {
+    let resolution = 50;
+    curves
+        .0
+        .iter()
+        .enumerate()
+        .filter_map(|(chain, curve)| curve.as_ref().map(|curve| (chain, curve)))
+        .flat_map(|(chain, curve)| {
+            curve
+                .segments()
+                .iter()
+                .enumerate()
+                .flat_map(move |(i, segment)| {
+                    (0..=resolution).map(move |step| {
+                        let t = step as f32 / resolution as f32;
+                        (chain, i, segment.position(t).xz().distance(position.xz()))
+                    })
+                })
+        })
+        .filter(|(_, _, distance)| *distance < GRAB_RADIUS)
+        .min_by(|a, b| a.2.total_cmp(&b.2))
+        // Segment `i` runs from point `i` to point `i + 1`.
+        .map(|(chain, i, _)| PointId {
+            chain,
+            index: i + 1,
+        })
+}
+
+fn draw_control_points(
+    control_points: Res<ControlPoints>,
+    drag: Res<PointDrag>,
+    mut gizmos: Gizmos,
+) {
+    for (chain, c) in control_points.chains.iter().enumerate() {
+        for (index, point) in c.points.iter().enumerate() {
+            let color = if drag.hovered == Some(PointId { chain, index }) {
+                HOVERED_POINT_COLOR
+            } else if chain == control_points.active {
+                POINT_COLOR
+            } else {
+                INACTIVE_POINT_COLOR
+            };
+            gizmos.sphere(Isometry3d::from_translation(*point), POINT_RADIUS, color);
         }
-        sim.state = SimulationState::Physics;
+        if let (Some(_), Some(fork)) = (c.parent, c.points.first()) {
+            gizmos.circle(
+                Isometry3d::new(*fork, Quat::from_rotation_arc(Vec3::Z, Vec3::Y)),
+                GRAB_RADIUS,
+                FORK_COLOR,
+            );
+        }
+    }
+    if let Some(stroke) = &drag.stroke {
+        gizmos.linestrip(stroke.iter().copied(), STROKE_COLOR);
     }
 }
 
 // #[hot]
-fn animate_bump(
-    control_points: Res<ControlPoints>,
-    sim: ResMut<CurrentSimulation>,
-    mut pusher_transform: Single<&mut Transform, With<Pusher>>,
-    time: Res<Time>,
+fn handle_undo(
+    actions: Res<ActionState>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+    layout: Res<LevelLayout>,
 ) {
-    // println!("{:?}", sim.state);
-    if sim.state == SimulationState::Physics {
-        let distance = DOMINO_DISTANCE * 1.75;
-        let toward = if control_points.points.len() > 1 {
-            control_points.points[1]
-        } else {
-            pusher_transform.translation
+    if actions.just_pressed(Action::Redo) {
+        history.redo(&mut control_points);
+    } else if actions.just_pressed(Action::Undo) {
+        history.undo(&mut control_points);
+    }
+    let clear = actions.just_pressed(Action::Clear);
+    let has_path = control_points.chains.len() > 1 || control_points.main_points().len() > 1;
+    if clear && has_path {
+        let edit = CurveEdit::Clear {
+            before: control_points.chains.clone(),
+            start: layout.control_start(),
         };
+        history.apply(edit, &mut control_points);
+    }
+}
 
-        if pusher_transform
-            .translation
-            .distance(control_points.points[0])
-            < distance
-        {
-            pusher_transform
-                .translation
-                .smooth_nudge(&toward, 0.04, time.delta_secs());
-        }
+// #[hot]
+fn handle_start_sim(
+    actions: Res<ActionState>,
+    is_all_valid: Res<IsAllValid>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Start) && is_all_valid.value {
+        next_state.set(GameState::Simulating);
     }
 }
 
@@ -224,54 +636,69 @@ fn spawn_markers(
     commands: &mut Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    curve: Res<Curve>,
+    curves: Res<Curves>,
+    control_points: Res<ControlPoints>,
+    layout: &LevelDefinition,
 ) {
-    let Some(ref curve) = curve.0 else {
-        return;
-    };
-
-    let spacing = domino::DOMINO_DISTANCE;
-    let resolution = 1000;
-    let mut last_pos = Vec3::ZERO;
-    let mut pos: Vec3;
-    let mut dist_accum = 0.0;
-    let mut next_dist = spacing;
-
-    for segment in curve.segments() {
-        if last_pos == Vec3::ZERO {
-            last_pos = segment.position(0.0);
-        }
-        for i in 1..=resolution {
-            let t = i as f32 / resolution as f32;
-            pos = segment.position(t);
-            let step = pos.distance(last_pos);
-            dist_accum += step;
-            if step > spacing {}
-
-            if dist_accum >= next_dist {
-                commands.spawn((
-                    Name::new("Domino Marker"),
-                    DominoMarker,
-                    Collider::cuboid(
-                        domino::DOMINO_HALF_SIZE.x,
-                        domino::DOMINO_HALF_SIZE.y,
-                        domino::DOMINO_HALF_SIZE.z,
-                    ),
-                    Sensor,
-                    CollidingEntities::default(),
-                    ActiveCollisionTypes::all(),
-                    ActiveEvents::COLLISION_EVENTS,
-                    Mesh3d(meshes.add(Cuboid::from_size(domino::DOMINO_SIZE))),
-                    MeshMaterial3d(materials.add(VALID_COLOR)),
-                    Transform::from_translation(pos).looking_at(last_pos, Dir3::Y),
-                ));
-                next_dist += spacing;
-            }
-            last_pos = pos;
+    for (chain, curve) in curves.0.iter().enumerate() {
+        if let Some(curve) = curve {
+            let kinds = &control_points.chains[chain].kinds;
+            let placements = placement::place_dominoes(curve, kinds, control_points.spacing);
+            spawn_chain_markers(
+                commands,
+                &mut meshes,
+                &mut materials,
+                &placements,
+                chain,
+                layout,
+            );
         }
     }
 }
 
+/// Spawns a marker at each placement along a chain, standing on the ground under it
+/// and square to its surface.
+pub fn spawn_chain_markers(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    placements: &[Placement],
+    chain: usize,
+    layout: &LevelDefinition,
+) {
+    let analyses = analysis::analyse_path(placements);
+    for (index, (placement, analysis)) in placements.iter().zip(analyses).enumerate() {
+        let Placement {
+            position,
+            tangent,
+            kind,
+            ..
+        } = *placement;
+        let half_size = kind.half_size();
+        let surface = layout.surface_at(position.xz());
+        let standing = vec3(position.x, surface.height, position.z)
+            + surface.normal * (half_size.y + domino::DOMINO_Y_OFFSET);
+        let back = (-tangent).reject_from(surface.normal);
+        commands.spawn((
+            Name::new("Domino Marker"),
+            DominoMarker {
+                chain,
+                index,
+                kind,
+                risk: analysis.risk,
+            },
+            Collider::cuboid(half_size.x, half_size.y, half_size.z),
+            Sensor,
+            CollidingEntities::default(),
+            ActiveCollisionTypes::all(),
+            ActiveEvents::COLLISION_EVENTS,
+            Mesh3d(meshes.add(Cuboid::from_size(kind.size()))),
+            MeshMaterial3d(materials.add(kind.marker_material())),
+            Transform::from_translation(standing).looking_to(back, surface.normal),
+        ));
+    }
+}
+
 // #[hot]
 pub fn despawn_entities<T: Component>(commands: &mut Commands, mut query: Query<Entity, With<T>>) {
     for marker in query.iter_mut() {
@@ -0,0 +1,265 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use crate::{
+    curve::{Chain, ControlPoints},
+    domino::DominoKind,
+    path_tool::SegmentShape,
+    placement::MarkerSpacing,
+};
+
+/// How many edits can be undone before the oldest are forgotten.
+pub const HISTORY_LIMIT: usize = 200;
+
+/// A single reversible change to the control points.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CurveEdit {
+    Add {
+        chain: usize,
+        position: Vec3,
+        shape: SegmentShape,
+        /// How many segment shapes the chain had before, since adding may extend them.
+        shape_count: usize,
+    },
+    /// Points from a freehand stroke, added to the end of a chain together.
+    AddStroke {
+        chain: usize,
+        positions: Vec<Vec3>,
+        shape_count: usize,
+    },
+    Insert {
+        chain: usize,
+        index: usize,
+        position: Vec3,
+    },
+    Move {
+        chain: usize,
+        index: usize,
+        from: Vec3,
+        to: Vec3,
+    },
+    Delete {
+        chain: usize,
+        index: usize,
+        position: Vec3,
+        /// The chain's segment kinds and shapes before the point went,
+        /// since one segment goes with it.
+        kinds: Vec<DominoKind>,
+        shapes: Vec<SegmentShape>,
+    },
+    SetKind {
+        chain: usize,
+        segment: usize,
+        from: DominoKind,
+        to: DominoKind,
+    },
+    AddChain {
+        chain: Chain,
+    },
+    Clear {
+        before: Vec<Chain>,
+        start: Vec3,
+    },
+    SetSpacing {
+        from: MarkerSpacing,
+        to: MarkerSpacing,
+    },
+    /// Every chain swapped for another path, such as a saved one or the one from a replay.
+    Replace {
+        before: Vec<Chain>,
+        after: Vec<Chain>,
+    },
+}
+
+impl CurveEdit {
+    fn apply(&self, control_points: &mut ControlPoints) {
+        let chains = &mut control_points.chains;
+        match self {
+            CurveEdit::Add {
+                chain,
+                position,
+                shape,
+                ..
+            } => chains[*chain].push_point(*position, *shape),
+            CurveEdit::AddStroke {
+                chain, positions, ..
+            } => {
+                for position in positions {
+                    chains[*chain].push_point(*position, SegmentShape::Smooth);
+                }
+            }
+            CurveEdit::Insert {
+                chain,
+                index,
+                position,
+            } => chains[*chain].insert_point(*index, *position),
+            CurveEdit::Move {
+                chain, index, to, ..
+            } => chains[*chain].points[*index] = *to,
+            CurveEdit::Delete { chain, index, .. } => chains[*chain].remove_point(*index),
+            CurveEdit::SetKind {
+                chain, segment, to, ..
+            } => chains[*chain].set_kind(*segment, *to),
+            CurveEdit::AddChain { chain } => {
+                chains.push(chain.clone());
+                control_points.active = chains.len() - 1;
+            }
+            CurveEdit::Clear { start, .. } => control_points.reset(*start),
+            CurveEdit::SetSpacing { to, .. } => control_points.spacing = *to,
+            CurveEdit::Replace { after, .. } => {
+                chains.clone_from(after);
+                control_points.active = 0;
+            }
+        }
+    }
+
+    fn revert(&self, control_points: &mut ControlPoints) {
+        let chains = &mut control_points.chains;
+        match self {
+            CurveEdit::Add {
+                chain, shape_count, ..
+            } => {
+                chains[*chain].points.pop();
+                chains[*chain].shapes.truncate(*shape_count);
+            }
+            CurveEdit::AddStroke {
+                chain,
+                positions,
+                shape_count,
+            } => {
+                let points = &mut chains[*chain].points;
+                points.truncate(points.len().saturating_sub(positions.len()));
+                chains[*chain].shapes.truncate(*shape_count);
+            }
+            CurveEdit::Insert { chain, index, .. } => chains[*chain].remove_point(*index),
+            CurveEdit::Move {
+                chain, index, from, ..
+            } => chains[*chain].points[*index] = *from,
+            CurveEdit::Delete {
+                chain,
+                index,
+                position,
+                kinds,
+                shapes,
+            } => {
+                chains[*chain].points.insert(*index, *position);
+                chains[*chain].kinds.clone_from(kinds);
+                chains[*chain].shapes.clone_from(shapes);
+            }
+            CurveEdit::SetKind {
+                chain,
+                segment,
+                from,
+                ..
+            } => chains[*chain].set_kind(*segment, *from),
+            CurveEdit::AddChain { .. } => {
+                chains.pop();
+            }
+            CurveEdit::Clear { before, .. } | CurveEdit::Replace { before, .. } => {
+                chains.clone_from(before)
+            }
+            CurveEdit::SetSpacing { from, .. } => control_points.spacing = *from,
+        }
+        control_points.active = control_points.active.min(chains.len().saturating_sub(1));
+    }
+}
+
+/// Undo and redo stacks for every edit made to [`ControlPoints`].
+#[derive(Resource, Default, Debug)]
+pub struct CurveHistory {
+    undo: VecDeque<CurveEdit>,
+    redo: Vec<CurveEdit>,
+}
+
+impl CurveHistory {
+    /// Applies an edit and records it, discarding anything that could have been redone.
+    pub fn apply(&mut self, edit: CurveEdit, control_points: &mut ControlPoints) {
+        edit.apply(control_points);
+        self.record(edit);
+    }
+
+    /// Records an edit that has already been applied, such as a finished drag.
+    pub fn record(&mut self, edit: CurveEdit) {
+        self.redo.clear();
+        self.undo.push_back(edit);
+        if self.undo.len() > HISTORY_LIMIT {
+            self.undo.pop_front();
+        }
+    }
+
+    /// Returns false when there is nothing left to undo.
+    pub fn undo(&mut self, control_points: &mut ControlPoints) -> bool {
+        let Some(edit) = self.undo.pop_back() else {
+            return false;
+        };
+        edit.revert(control_points);
+        self.redo.push(edit);
+        true
+    }
+
+    /// Returns false when there is nothing left to redo.
+    pub fn redo(&mut self, control_points: &mut ControlPoints) -> bool {
+        let Some(edit) = self.redo.pop() else {
+            return false;
+        };
+        edit.apply(control_points);
+        self.undo.push_back(edit);
+        true
+    }
+
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn add(control_points: &ControlPoints, position: Vec3, shape: SegmentShape) -> CurveEdit {
+        CurveEdit::Add {
+            chain: 0,
+            position,
+            shape,
+            shape_count: control_points.chains[0].shapes.len(),
+        }
+    }
+
+    #[test]
+    fn undoing_an_add_restores_segment_shapes() {
+        let mut control_points = ControlPoints::default();
+        control_points.reset(Vec3::ZERO);
+        let mut history = CurveHistory::default();
+        let edit = add(&control_points, Vec3::X, SegmentShape::Smooth);
+        history.apply(edit, &mut control_points);
+        let before = control_points.chains.clone();
+
+        let edit = add(&control_points, Vec3::Z, SegmentShape::Arc);
+        history.apply(edit, &mut control_points);
+        let strokes = CurveEdit::AddStroke {
+            chain: 0,
+            positions: vec![Vec3::ONE, Vec3::NEG_ONE],
+            shape_count: control_points.chains[0].shapes.len(),
+        };
+        history.apply(strokes, &mut control_points);
+        assert!(history.undo(&mut control_points));
+        assert!(history.undo(&mut control_points));
+        assert_eq!(control_points.chains, before);
+    }
+
+    #[test]
+    fn spacing_changes_can_be_undone() {
+        let mut control_points = ControlPoints::default();
+        let mut history = CurveHistory::default();
+        let edit = CurveEdit::SetSpacing {
+            from: MarkerSpacing::Fixed,
+            to: MarkerSpacing::Adaptive,
+        };
+        history.apply(edit, &mut control_points);
+        assert_eq!(control_points.spacing, MarkerSpacing::Adaptive);
+        assert!(history.undo(&mut control_points));
+        assert_eq!(control_points.spacing, MarkerSpacing::Fixed);
+    }
+}
@@ -1,7 +1,13 @@
+use std::collections::BTreeMap;
+
 use bevy::prelude::*;
-use bevy_rapier3d::prelude::CollidingEntities;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::environment;
+use crate::{
+    analysis::Risk, environment, game_state::GameState, level::LevelLayout,
+    physics_profile::DominoPhysicsProfile, pusher::Pusher,
+};
 
 pub const DOMINO_SIZE: Vec3 = Vec3::new(1., 2., 0.2);
 pub const DOMINO_HALF_SIZE: Vec3 = Vec3::new(
@@ -11,23 +17,237 @@ pub const DOMINO_HALF_SIZE: Vec3 = Vec3::new(
 );
 pub const DOMINO_DISTANCE: f32 = DOMINO_SIZE.y * 0.6;
 pub const DOMINO_Y_OFFSET: f32 = 0.025;
-pub const DOMINO_Y_POS: f32 = environment::FLOOR_HALF_SIZE.y + DOMINO_HALF_SIZE.y + DOMINO_Y_OFFSET;
+pub const DOMINO_Y_POS: f32 = standing_y(environment::FLOOR_HALF_SIZE.y);
 
 pub const VALID_COLOR: Color = Color::srgba(0.2, 0.8, 0.2, 0.9);
 pub const INVALID_COLOR: Color = Color::srgba(0.5, 0.1, 0.1, 0.9);
+/// Markers that can be placed but may not be knocked over by the one before.
+pub const RISKY_COLOR: Color = Color::srgba(0.9, 0.6, 0.1, 0.9);
+
+/// Height of a standard domino's centre when it stands on ground this high.
+pub const fn standing_y(surface_height: f32) -> f32 {
+    surface_height + DOMINO_HALF_SIZE.y + DOMINO_Y_OFFSET
+}
+
+/// How strongly a marker glows in its kind's colour, so special dominoes stand out
+/// before the physics starts.
+const MARKER_TINT: f32 = 0.6;
+
+/// Lean from upright at which a domino counts as tipping.
+const TIPPING_ANGLE: f32 = 5f32.to_radians();
+/// Lean at which a domino has passed the point of no return.
+const FALLEN_ANGLE: f32 = 45f32.to_radians();
+
+/// The kinds of domino a path can be built from, chosen per control-point segment.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DominoKind {
+    #[default]
+    Standard,
+    /// Four times as dense and grippier, so it keeps going where a light one would stall.
+    Heavy,
+    /// Taller and spaced further apart, so it knocks over a domino past a gap.
+    Tall,
+    /// Presses a switch it falls on or beside, setting off that switch's mechanisms.
+    Trigger,
+    /// A long, light plank that falls across a small gap and lands on the far side.
+    Bridge,
+}
+
+impl DominoKind {
+    pub const ALL: [DominoKind; 5] = [
+        DominoKind::Standard,
+        DominoKind::Heavy,
+        DominoKind::Tall,
+        DominoKind::Trigger,
+        DominoKind::Bridge,
+    ];
+
+    pub fn size(self) -> Vec3 {
+        match self {
+            DominoKind::Standard | DominoKind::Heavy | DominoKind::Trigger => DOMINO_SIZE,
+            DominoKind::Tall => Vec3::new(1.2, 3., 0.25),
+            DominoKind::Bridge => Vec3::new(1., 4., 0.15),
+        }
+    }
+
+    pub fn half_size(self) -> Vec3 {
+        self.size() * 0.5
+    }
+
+    /// Distance from a domino of this kind to the next one along the path.
+    pub fn spacing(self) -> f32 {
+        self.size().y * 0.6
+    }
+
+    pub fn density(self) -> f32 {
+        match self {
+            DominoKind::Heavy => 4.,
+            DominoKind::Bridge => 0.5,
+            _ => 1.,
+        }
+    }
+
+    pub fn friction(self) -> f32 {
+        match self {
+            DominoKind::Heavy => 0.8,
+            DominoKind::Bridge => 0.3,
+            _ => 0.5,
+        }
+    }
+
+    pub fn color(self) -> Color {
+        match self {
+            DominoKind::Standard => Color::WHITE,
+            DominoKind::Heavy => Color::srgb(0.3, 0.3, 0.35),
+            DominoKind::Tall => Color::srgb(0.4, 0.6, 1.),
+            DominoKind::Trigger => Color::srgb(1., 0.75, 0.1),
+            DominoKind::Bridge => Color::srgb(0.65, 0.45, 0.25),
+        }
+    }
+
+    /// The kind after this one, wrapping around.
+    pub fn next(self) -> Self {
+        let index = Self::ALL.iter().position(|kind| *kind == self).unwrap_or(0);
+        Self::ALL[(index + 1) % Self::ALL.len()]
+    }
+
+    pub fn marker_material(self) -> StandardMaterial {
+        let emissive = if self == DominoKind::Standard {
+            LinearRgba::BLACK
+        } else {
+            self.color().to_linear() * MARKER_TINT
+        };
+        StandardMaterial {
+            emissive,
+            ..VALID_COLOR.into()
+        }
+    }
+}
 
 #[derive(Component)]
-pub struct Domino;
+pub struct Domino {
+    pub chain: usize,
+    /// Position along the chain, counting from its start.
+    pub index: usize,
+    pub kind: DominoKind,
+}
+
+/// Sent once when a [`DominoKind::Trigger`] domino falls.
+#[derive(Event, Debug, Clone, Copy)]
+pub struct TriggerFallen {
+    pub domino: Entity,
+}
 
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum DominoState {
+    #[default]
+    Standing,
+    Tipping,
+    Fallen,
+    /// Leaning but asleep, usually propped up against the next domino.
+    Stalled,
+}
+
+/// How far a domino has toppled, updated every frame of the physics phase.
+#[derive(Component, Debug)]
+pub struct Topple {
+    pub state: DominoState,
+    /// Knocked over by the domino before it, which was itself knocked in sequence.
+    /// The first domino of a chain is knocked by the pusher or by another chain.
+    pub in_sequence: bool,
+    /// Put to sleep by Rapier and not being pushed along by the pusher, however slowly.
+    is_resting: bool,
+    /// The domino's up direction as placed, square to the ground under it.
+    upright: Vec3,
+}
+
+impl Topple {
+    pub fn new(upright: Vec3) -> Self {
+        Self {
+            state: DominoState::Standing,
+            in_sequence: false,
+            is_resting: false,
+            upright,
+        }
+    }
+}
+
+/// Marks the final domino of each chain.
 #[derive(Component)]
-pub struct DominoMarker;
+pub struct LastInChain;
 
 #[derive(Component)]
-pub struct DominoSensor;
+pub struct DominoMarker {
+    /// Index of the chain this marker was placed along.
+    pub chain: usize,
+    pub index: usize,
+    pub kind: DominoKind,
+    /// How likely the domino before is to knock this one over.
+    pub risk: Risk,
+}
+
+/// How far each chain has fallen during the physics phase, in chain order.
+#[derive(Resource, Default, Debug)]
+pub struct ChainProgress {
+    pub chains: Vec<ChainStatus>,
+}
+
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct ChainStatus {
+    pub total: usize,
+    pub fallen: usize,
+    /// The first domino left standing once the chain came to rest, if it stopped short.
+    pub broke_at: Option<usize>,
+    has_started: bool,
+    /// Every domino in the chain is resting.
+    is_resting: bool,
+}
+
+impl ChainProgress {
+    /// Whether the dominoes have started falling and every chain that started has since
+    /// come to rest, so nothing more will happen.
+    pub fn has_stopped(&self) -> bool {
+        let mut started = self
+            .chains
+            .iter()
+            .filter(|status| status.has_started)
+            .peekable();
+        started.peek().is_some() && started.all(|status| status.is_resting)
+    }
+}
+
+impl ChainStatus {
+    pub fn describe(&self) -> String {
+        let mut value = format!("{} of {} fallen", self.fallen, self.total);
+        if let Some(index) = self.broke_at {
+            value.push_str(&format!(", chain broke at index {index}"));
+        }
+        value
+    }
+}
 
 #[derive(Resource, Debug, Default)]
 pub struct IsAllValid {
     pub value: bool,
+    /// Chains with at least one overlapping marker.
+    pub invalid_chains: Vec<usize>,
+    /// Chains with at least one risky marker. They can still be started.
+    pub risky_chains: Vec<usize>,
+    /// The first marker of each chain that likely won't be knocked over.
+    pub likely_breaks: BTreeMap<usize, usize>,
+}
+
+/// How many markers the current path places, against the level's limit if it has one.
+#[derive(Resource, Default, Debug)]
+pub struct DominoBudget {
+    pub used: usize,
+    pub max: Option<usize>,
+}
+
+impl DominoBudget {
+    pub fn is_over(&self) -> bool {
+        self.max.is_some_and(|max| self.used > max)
+    }
 }
 
 pub struct DominoPlugin;
@@ -35,24 +255,272 @@ pub struct DominoPlugin;
 impl Plugin for DominoPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(IsAllValid::default())
-            .add_systems(Update, detect_valid_markers);
+            .insert_resource(DominoBudget::default())
+            .insert_resource(ChainProgress::default())
+            .add_event::<TriggerFallen>()
+            .add_systems(OnEnter(GameState::Simulating), spawn_dominoes)
+            .add_systems(
+                Update,
+                (
+                    detect_valid_markers,
+                    (
+                        update_topple,
+                        light_triggers,
+                        update_chain_progress,
+                        show_break,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Simulating)),
+                ),
+            );
+    }
+}
+
+/// Replaces every marker with a physical domino, marking the last one of each chain.
+pub fn spawn_dominoes(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    query: Query<(Entity, &DominoMarker, &Transform)>,
+    mut progress: ResMut<ChainProgress>,
+    (profile, layout): (Res<DominoPhysicsProfile>, Res<LevelLayout>),
+) {
+    progress.chains.clear();
+    let profile = profile.with_overrides(&layout.physics);
+    let mut chain_lengths = Vec::new();
+    for (_, marker, _) in query.iter() {
+        if chain_lengths.len() <= marker.chain {
+            chain_lengths.resize(marker.chain + 1, 0);
+        }
+        chain_lengths[marker.chain] = chain_lengths[marker.chain].max(marker.index + 1);
+    }
+    for (entity, marker, transform) in query.iter() {
+        let pos = transform.translation;
+        let rot = transform.rotation;
+        let kind = marker.kind;
+        let half_size = kind.half_size();
+        commands.entity(entity).despawn();
+        let mut domino = commands.spawn((
+            Domino {
+                chain: marker.chain,
+                index: marker.index,
+                kind,
+            },
+            Topple::new(transform.up().as_vec3()),
+            RigidBody::Dynamic,
+            Velocity::default(),
+            Sleeping::default(),
+            Collider::cuboid(half_size.x, half_size.y, half_size.z),
+            profile.domino_physics(kind),
+            Mesh3d(meshes.add(Cuboid::from_size(kind.size()))),
+            MeshMaterial3d(materials.add(kind.color())),
+            Transform::from_translation(pos).with_rotation(rot),
+            CollidingEntities::default(),
+            ActiveEvents::COLLISION_EVENTS,
+        ));
+        if marker.index + 1 == chain_lengths[marker.chain] {
+            domino.insert(LastInChain);
+        }
     }
 }
 
 fn detect_valid_markers(
-    query: Query<(&CollidingEntities, &mut MeshMaterial3d<StandardMaterial>), With<DominoMarker>>,
+    query: Query<(
+        &DominoMarker,
+        &CollidingEntities,
+        &mut MeshMaterial3d<StandardMaterial>,
+    )>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut is_all_valid: ResMut<IsAllValid>,
+    mut budget: ResMut<DominoBudget>,
+    layout: Res<LevelLayout>,
 ) {
+    // Markers count against the budget in chain order, so the end of the last chain
+    // is the first to go over.
+    let mut chain_lengths: Vec<usize> = Vec::new();
+    for (marker, ..) in query.iter() {
+        if chain_lengths.len() <= marker.chain {
+            chain_lengths.resize(marker.chain + 1, 0);
+        }
+        chain_lengths[marker.chain] = chain_lengths[marker.chain].max(marker.index + 1);
+    }
+    let chain_starts: Vec<usize> = chain_lengths
+        .iter()
+        .scan(0, |start, length| {
+            let chain_start = *start;
+            *start += length;
+            Some(chain_start)
+        })
+        .collect();
+    let used = chain_lengths.iter().sum();
+    let max = layout.max_dominoes.map(|max| max as usize);
+    if budget.used != used || budget.max != max {
+        *budget = DominoBudget { used, max };
+    }
+
     is_all_valid.value = true;
-    for (colliding, material_handle) in query.iter() {
+    is_all_valid.invalid_chains.clear();
+    is_all_valid.risky_chains.clear();
+    is_all_valid.likely_breaks.clear();
+    for (marker, colliding, material_handle) in query.iter() {
+        let is_over_budget =
+            max.is_some_and(|max| chain_starts[marker.chain] + marker.index >= max);
         if let Some(material) = materials.get_mut(material_handle) {
-            material.base_color = if colliding.is_empty() {
+            material.base_color = if is_over_budget {
+                is_all_valid.value = false;
+                INVALID_COLOR
+            } else if colliding.is_empty() && marker.risk != Risk::Safe {
+                if marker.risk == Risk::Breaks {
+                    let first = is_all_valid
+                        .likely_breaks
+                        .entry(marker.chain)
+                        .or_insert(marker.index);
+                    *first = (*first).min(marker.index);
+                } else if !is_all_valid.risky_chains.contains(&marker.chain) {
+                    is_all_valid.risky_chains.push(marker.chain);
+                }
+                RISKY_COLOR
+            } else if colliding.is_empty() {
                 VALID_COLOR
             } else {
                 is_all_valid.value = false;
+                if !is_all_valid.invalid_chains.contains(&marker.chain) {
+                    is_all_valid.invalid_chains.push(marker.chain);
+                }
                 INVALID_COLOR
             };
         }
     }
+    is_all_valid.invalid_chains.sort_unstable();
+    is_all_valid.risky_chains.sort_unstable();
+}
+
+fn update_topple(
+    mut query: Query<(
+        Entity,
+        &Domino,
+        &Transform,
+        &Sleeping,
+        &CollidingEntities,
+        &mut Topple,
+    )>,
+    pusher_query: Query<(), With<Pusher>>,
+    mut triggers: EventWriter<TriggerFallen>,
+) {
+    // Which dominoes were knocked in sequence as of the last frame.
+    let in_sequence: Vec<(Entity, usize, usize)> = query
+        .iter()
+        .filter(|(.., topple)| topple.in_sequence)
+        .map(|(entity, domino, ..)| (entity, domino.chain, domino.index))
+        .collect();
+    let is_knocker = |domino: &Domino, other: Entity| {
+        if domino.index == 0 && pusher_query.contains(other) {
+            return true;
+        }
+        in_sequence.iter().any(|&(entity, chain, index)| {
+            entity == other
+                && if domino.index == 0 {
+                    chain != domino.chain
+                } else {
+                    chain == domino.chain && index + 1 == domino.index
+                }
+        })
+    };
+
+    for (entity, domino, transform, sleeping, colliding, mut topple) in query.iter_mut() {
+        let is_falling = matches!(topple.state, DominoState::Standing | DominoState::Tipping);
+        if !topple.in_sequence && is_falling && colliding.iter().any(|e| is_knocker(domino, e)) {
+            topple.in_sequence = true;
+        }
+
+        let lean = transform.up().angle_between(topple.upright);
+        let is_pushed = colliding.iter().any(|e| pusher_query.contains(e));
+        topple.is_resting = sleeping.sleeping && !is_pushed;
+        let was_fallen = topple.state == DominoState::Fallen;
+        topple.state = if lean >= FALLEN_ANGLE || topple.state == DominoState::Fallen {
+            DominoState::Fallen
+        } else if lean < TIPPING_ANGLE {
+            DominoState::Standing
+        } else if topple.is_resting {
+            DominoState::Stalled
+        } else {
+            DominoState::Tipping
+        };
+        if domino.kind == DominoKind::Trigger && !was_fallen && topple.state == DominoState::Fallen
+        {
+            triggers.write(TriggerFallen { domino: entity });
+        }
+    }
+}
+
+fn update_chain_progress(query: Query<(&Domino, &Topple)>, mut progress: ResMut<ChainProgress>) {
+    let mut states: Vec<Vec<DominoState>> = Vec::new();
+    let mut is_resting = Vec::new();
+    for (domino, topple) in query.iter() {
+        if states.len() <= domino.chain {
+            states.resize(domino.chain + 1, Vec::new());
+            is_resting.resize(domino.chain + 1, true);
+        }
+        let chain = &mut states[domino.chain];
+        if chain.len() <= domino.index {
+            chain.resize(domino.index + 1, DominoState::Standing);
+        }
+        chain[domino.index] = topple.state;
+        is_resting[domino.chain] &= topple.is_resting;
+    }
+    progress.chains.resize(states.len(), ChainStatus::default());
+
+    for ((status, states), is_resting) in progress.chains.iter_mut().zip(&states).zip(is_resting) {
+        status.total = states.len();
+        status.fallen = states
+            .iter()
+            .filter(|state| **state == DominoState::Fallen)
+            .count();
+        status.is_resting = is_resting;
+        // A chain that hasn't started yet may still be waiting for its fork.
+        status.has_started = states.iter().any(|state| *state != DominoState::Standing);
+        status.broke_at = if status.has_started && is_resting {
+            states
+                .iter()
+                .position(|state| *state != DominoState::Fallen)
+        } else {
+            None
+        };
+    }
+}
+
+/// Makes fallen trigger dominoes glow, so it's clear which mechanisms they set off.
+fn light_triggers(
+    mut triggers: EventReader<TriggerFallen>,
+    query: Query<&MeshMaterial3d<StandardMaterial>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for trigger in triggers.read() {
+        if let Ok(material_handle) = query.get(trigger.domino)
+            && let Some(material) = materials.get_mut(material_handle)
+        {
+            material.emissive = DominoKind::Trigger.color().to_linear();
+        }
+    }
+}
+
+/// Highlights the domino each broken chain stopped at.
+fn show_break(
+    query: Query<(&Domino, &MeshMaterial3d<StandardMaterial>)>,
+    progress: Res<ChainProgress>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    if !progress.is_changed() {
+        return;
+    }
+    for (domino, material_handle) in query.iter() {
+        let Some(status) = progress.chains.get(domino.chain) else {
+            continue;
+        };
+        if status.broke_at == Some(domino.index)
+            && let Some(material) = materials.get_mut(material_handle)
+        {
+            material.base_color = INVALID_COLOR;
+        }
+    }
 }
@@ -0,0 +1,361 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::CollidingEntities;
+// use bevy_simple_subsecond_system::hot;
+
+use crate::{
+    bindings::{Action, ActionState},
+    cursor::Cursor,
+    domino::{self, INVALID_COLOR},
+    environment,
+    game_state::GameState,
+    goal::{GOAL_HALF_WIDTH, Goal},
+    level::{self, LevelLayout, WALL_COLOR, Wall},
+    level_asset::{LevelDefinition, WallDefinition},
+    pusher::Pusher,
+};
+
+pub const EDITOR_SAVE_PATH: &str = "assets/levels/custom.level.ron";
+const DEFAULT_WALL_SIZE: Vec3 = vec3(
+    environment::FLOOR_LENGTH * 0.4,
+    domino::DOMINO_SIZE.y * 0.9,
+    domino::DOMINO_DISTANCE,
+);
+const ROTATION_STEP: f32 = std::f32::consts::PI / 12.;
+const SELECTED_COLOR: Color = Color::srgb(1., 1., 0.);
+
+/// Entities a wall must not overlap.
+type Blocker = Or<(With<Goal>, With<Pusher>)>;
+
+#[derive(Default, PartialEq, Debug, Clone, Copy)]
+pub enum EditorTool {
+    #[default]
+    Wall,
+    Goal,
+    Pusher,
+}
+
+#[derive(Resource, Default, Debug)]
+pub struct EditorState {
+    pub tool: EditorTool,
+    pub selected: Option<Entity>,
+    pub is_dragging: bool,
+    /// Offset from the cursor to the centre of the wall being dragged.
+    grab_offset: Vec2,
+    /// Index of the goal being placed with the goal tool.
+    pub selected_goal: Option<usize>,
+    /// False while any wall overlaps the goal or the pusher.
+    pub is_valid: bool,
+    /// Whether any wall has been added, moved, turned or removed since the editor opened.
+    pub has_edited_walls: bool,
+}
+
+pub struct EditorPlugin;
+
+impl Plugin for EditorPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(EditorState::default())
+            .add_systems(OnEnter(GameState::Editing), open_editor)
+            .add_systems(OnExit(GameState::Editing), close_editor)
+            .add_systems(
+                Update,
+                toggle_editor.run_if(in_state(GameState::Drawing).or(in_state(GameState::Editing))),
+            )
+            .add_systems(
+                Update,
+                (
+                    select_tool,
+                    grab_wall,
+                    drag_wall,
+                    place_actor,
+                    edit_selected_wall,
+                    delete_selected_goal,
+                    validate_walls,
+                    draw_selection,
+                    save_level,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Editing)),
+            );
+    }
+}
+
+// #[hot]
+fn toggle_editor(
+    actions: Res<ActionState>,
+    state: Res<State<GameState>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(Action::Editor) {
+        return;
+    }
+    next_state.set(if *state.get() == GameState::Editing {
+        GameState::Drawing
+    } else {
+        GameState::Editing
+    });
+}
+
+fn open_editor(mut editor: ResMut<EditorState>) {
+    *editor = EditorState {
+        is_valid: true,
+        ..default()
+    };
+}
+
+/// Keeps the edited walls so the level plays as it was left in the editor.
+/// The layout is left untouched otherwise, so the path drawn on it is kept.
+fn close_editor(
+    editor: Res<EditorState>,
+    mut layout: ResMut<LevelLayout>,
+    wall_query: Query<(&Wall, &Transform)>,
+) {
+    if editor.has_edited_walls {
+        layout.0 = current_definition(&layout, &wall_query);
+    }
+}
+
+fn select_tool(actions: Res<ActionState>, mut editor: ResMut<EditorState>) {
+    let tool = if actions.just_pressed(Action::EditWalls) {
+        EditorTool::Wall
+    } else if actions.just_pressed(Action::EditGoals) {
+        EditorTool::Goal
+    } else if actions.just_pressed(Action::EditPusher) {
+        EditorTool::Pusher
+    } else {
+        return;
+    };
+    editor.tool = tool;
+    editor.selected = None;
+    editor.selected_goal = None;
+    editor.is_dragging = false;
+}
+
+// #[hot]
+fn grab_wall(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    mut editor: ResMut<EditorState>,
+    wall_query: Query<(Entity, &Wall, &Transform)>,
+) {
+    if editor.tool != EditorTool::Wall || !actions.just_pressed(Action::EditorPlace) {
+        return;
+    }
+
+    let hovered = wall_query
+        .iter()
+        .find(|(_, wall, transform)| contains_point(wall, transform, cursor.position));
+    let (entity, centre) = match hovered {
+        Some((entity, _, transform)) => (entity, transform.translation.xz()),
+        None => {
+            let wall = WallDefinition {
+                position: cursor.position.xz(),
+                size: DEFAULT_WALL_SIZE,
+                rotation: 0.,
+            };
+            let entity = level::spawn_wall(&mut commands, &mut meshes, &mut materials, &wall);
+            editor.has_edited_walls = true;
+            (entity, wall.position)
+        }
+    };
+    editor.selected = Some(entity);
+    editor.is_dragging = true;
+    editor.grab_offset = centre - cursor.position.xz();
+}
+
+fn drag_wall(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    mut editor: ResMut<EditorState>,
+    mut wall_query: Query<&mut Transform, With<Wall>>,
+) {
+    if !editor.is_dragging {
+        return;
+    }
+    if !actions.pressed(Action::EditorPlace) {
+        editor.is_dragging = false;
+        return;
+    }
+    let Some(mut transform) = editor.selected.and_then(|e| wall_query.get_mut(e).ok()) else {
+        return;
+    };
+    let position = cursor.position.xz() + editor.grab_offset;
+    if transform.translation.xz() != position {
+        transform.translation.x = position.x;
+        transform.translation.z = position.y;
+        editor.has_edited_walls = true;
+    }
+}
+
+fn place_actor(
+    actions: Res<ActionState>,
+    cursor: Res<Cursor>,
+    mut editor: ResMut<EditorState>,
+    mut layout: ResMut<LevelLayout>,
+) {
+    let position = cursor.position.xz();
+    if editor.tool == EditorTool::Goal && actions.just_pressed(Action::EditorPlace) {
+        // Grab the goal under the cursor, or add a new one.
+        let grabbed = layout
+            .goals
+            .iter()
+            .position(|goal| goal.distance(position) < GOAL_HALF_WIDTH);
+        editor.selected_goal = Some(grabbed.unwrap_or_else(|| {
+            layout.0.goals.push(position);
+            layout.goals.len() - 1
+        }));
+    }
+    if !actions.pressed(Action::EditorPlace) {
+        return;
+    }
+    // Only write to the layout when something moves, since changing it resets the path.
+    match editor.tool {
+        EditorTool::Goal => {
+            if let Some(index) = editor.selected_goal
+                && layout
+                    .goals
+                    .get(index)
+                    .is_some_and(|goal| *goal != position)
+            {
+                layout.0.goals[index] = position;
+            }
+        }
+        EditorTool::Pusher if layout.pusher != position => layout.0.pusher = position,
+        EditorTool::Pusher | EditorTool::Wall => {}
+    }
+}
+
+fn delete_selected_goal(
+    actions: Res<ActionState>,
+    mut editor: ResMut<EditorState>,
+    mut layout: ResMut<LevelLayout>,
+) {
+    let Some(selected) = editor.selected_goal else {
+        return;
+    };
+    // Every level needs at least one goal.
+    if layout.goals.len() > 1
+        && selected < layout.goals.len()
+        && actions.just_pressed(Action::EditorDelete)
+    {
+        layout.0.goals.remove(selected);
+        editor.selected_goal = None;
+    }
+}
+
+fn edit_selected_wall(
+    mut commands: Commands,
+    actions: Res<ActionState>,
+    mut editor: ResMut<EditorState>,
+    mut wall_query: Query<&mut Transform, With<Wall>>,
+) {
+    let Some(selected) = editor.selected else {
+        return;
+    };
+    let Ok(mut transform) = wall_query.get_mut(selected) else {
+        editor.selected = None;
+        return;
+    };
+    if actions.just_pressed(Action::RotateWallLeft) {
+        transform.rotate_y(ROTATION_STEP);
+        editor.has_edited_walls = true;
+    }
+    if actions.just_pressed(Action::RotateWallRight) {
+        transform.rotate_y(-ROTATION_STEP);
+        editor.has_edited_walls = true;
+    }
+    if actions.just_pressed(Action::EditorDelete) {
+        commands.entity(selected).despawn();
+        editor.selected = None;
+        editor.is_dragging = false;
+        editor.has_edited_walls = true;
+    }
+}
+
+// #[hot]
+fn validate_walls(
+    wall_query: Query<(&CollidingEntities, &MeshMaterial3d<StandardMaterial>), With<Wall>>,
+    blocker_query: Query<(), Blocker>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    mut editor: ResMut<EditorState>,
+) {
+    // Walls may overlap each other and the floor, but must leave the goal and pusher clear.
+    editor.is_valid = true;
+    for (colliding, material_handle) in wall_query.iter() {
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.base_color = if colliding.iter().any(|e| blocker_query.contains(e)) {
+                editor.is_valid = false;
+                INVALID_COLOR
+            } else {
+                WALL_COLOR
+            };
+        }
+    }
+}
+
+fn draw_selection(
+    editor: Res<EditorState>,
+    wall_query: Query<(&Wall, &Transform)>,
+    mut gizmos: Gizmos,
+) {
+    let Some((wall, transform)) = editor.selected.and_then(|e| wall_query.get(e).ok()) else {
+        return;
+    };
+    gizmos.cuboid(transform.with_scale(wall.size * 1.05), SELECTED_COLOR);
+}
+
+// #[hot]
+fn save_level(
+    actions: Res<ActionState>,
+    editor: Res<EditorState>,
+    layout: Res<LevelLayout>,
+    wall_query: Query<(&Wall, &Transform)>,
+) {
+    if !actions.just_pressed(Action::SaveLevel) {
+        return;
+    }
+    if !editor.is_valid {
+        warn!("Not saving: a wall overlaps the goal or the pusher");
+        return;
+    }
+
+    let definition = current_definition(&layout, &wall_query);
+    match ron::ser::to_string_pretty(&definition, ron::ser::PrettyConfig::default()) {
+        Ok(text) => write_level_file(&text),
+        Err(err) => error!("Could not serialize level: {err}"),
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_level_file(text: &str) {
+    match std::fs::write(EDITOR_SAVE_PATH, text) {
+        Ok(()) => info!("Saved level to {EDITOR_SAVE_PATH}"),
+        Err(err) => error!("Could not write {EDITOR_SAVE_PATH}: {err}"),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_level_file(_text: &str) {
+    warn!("Saving levels is not supported on the web");
+}
+
+/// The layout as it currently stands in the world, with walls read back from their entities.
+fn current_definition(
+    layout: &LevelLayout,
+    wall_query: &Query<(&Wall, &Transform)>,
+) -> LevelDefinition {
+    LevelDefinition {
+        walls: wall_query
+            .iter()
+            .map(|(wall, transform)| WallDefinition::from_transform(transform, wall.size))
+            .collect(),
+        ..layout.0.clone()
+    }
+}
+
+fn contains_point(wall: &Wall, transform: &Transform, point: Vec3) -> bool {
+    let local = transform.rotation.inverse() * (point - transform.translation);
+    local.x.abs() <= wall.size.x * 0.5 && local.z.abs() <= wall.size.z * 0.5
+}
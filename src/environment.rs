@@ -73,9 +73,10 @@ fn setup_environment(
 
     commands.spawn((
         Name::new("Pusher"),
-        Pusher,
-        RigidBody::Fixed,
+        Pusher::default(),
+        RigidBody::KinematicPositionBased,
         Collider::ball(pusher::RADIUS),
+        CollidingEntities::default(),
         Mesh3d(meshes.add(Sphere {
             radius: pusher::RADIUS,
         })),
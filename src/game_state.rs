@@ -0,0 +1,88 @@
+use bevy::prelude::*;
+
+use crate::bindings::{Action, ActionState};
+
+/// How long the level intro shows before drawing starts on its own.
+pub const INTRO_SECONDS: f32 = 2.;
+
+/// The flow of the game, from loading through each level.
+#[derive(States, Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum GameState {
+    /// Waiting for the level files to load.
+    #[default]
+    Loading,
+    /// The level select screen.
+    MainMenu,
+    /// Shows the level's name and goals before drawing starts.
+    LevelIntro,
+    Drawing,
+    Simulating,
+    Won,
+    /// The chain stopped without winning.
+    Failed,
+    /// Won the last level of the campaign.
+    AllComplete,
+    Editing,
+    /// Plays back a recorded run, without physics.
+    Replaying,
+}
+
+impl GameState {
+    /// Whether dominoes have been placed and knocked over.
+    pub fn is_after_push(self) -> bool {
+        matches!(
+            self,
+            GameState::Simulating | GameState::Won | GameState::Failed | GameState::AllComplete
+        )
+    }
+}
+
+/// Run condition for systems that keep going once the dominoes have been pushed.
+pub fn after_push(state: Res<State<GameState>>) -> bool {
+    state.get().is_after_push()
+}
+
+#[derive(Resource)]
+struct IntroTimer(Timer);
+
+pub struct GameStatePlugin;
+
+impl Plugin for GameStatePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_state::<GameState>()
+            .enable_state_scoped_entities::<GameState>()
+            .add_systems(OnEnter(GameState::LevelIntro), start_intro)
+            .add_systems(
+                Update,
+                (
+                    finish_intro.run_if(in_state(GameState::LevelIntro)),
+                    stop_simulation.run_if(after_push),
+                ),
+            );
+    }
+}
+
+fn start_intro(mut commands: Commands) {
+    commands.insert_resource(IntroTimer(Timer::from_seconds(
+        INTRO_SECONDS,
+        TimerMode::Once,
+    )));
+}
+
+fn finish_intro(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    mut timer: ResMut<IntroTimer>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if timer.0.tick(time.delta()).finished() || actions.just_pressed(Action::SkipIntro) {
+        next_state.set(GameState::Drawing);
+    }
+}
+
+/// Goes back to drawing with the same path, whether or not the run is over.
+fn stop_simulation(actions: Res<ActionState>, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.just_pressed(Action::Stop) {
+        next_state.set(GameState::Drawing);
+    }
+}
@@ -1,7 +1,9 @@
 use crate::{
-    curve::{CurrentSimulation, SimulationState},
+    domino::{ChainProgress, Domino, LastInChain, Topple},
     environment,
+    game_state::GameState,
     level::*,
+    level_asset::WinCondition,
 };
 use bevy::prelude::*;
 use bevy_rapier3d::prelude::{
@@ -20,54 +22,247 @@ const WIN_COLOR: Color = Color::srgba(0., 0.75, 0.75, 0.3);
 const DEFAULT_COLOR: Color = Color::srgba(1., 1., 0.9, 0.1);
 
 #[derive(Component)]
-pub struct Goal;
+pub struct Goal {
+    /// Position of this goal in the level's goal list.
+    pub index: usize,
+}
+
+/// When each goal was hit during the current run, in seconds since the push.
+#[derive(Resource, Default, Debug)]
+pub struct GoalProgress {
+    pub elapsed: f32,
+    pub hits: Vec<GoalHit>,
+}
+
+#[derive(Default, Debug, Clone, Copy)]
+pub struct GoalHit {
+    pub first: Option<f32>,
+    /// First time the goal was hit by the last domino of a chain.
+    pub by_last_domino: Option<f32>,
+}
 
 pub struct GoalPlugin;
 
 impl Plugin for GoalPlugin {
     fn build(&self, app: &mut App) {
         app.insert_resource(Level::default())
-            .add_systems(Startup, setup_goal)
-            .add_systems(Update, detect_dominos);
+            .insert_resource(GoalProgress::default())
+            .add_systems(OnEnter(GameState::Drawing), reset_progress)
+            .add_systems(OnEnter(GameState::Simulating), reset_progress)
+            .add_systems(
+                Update,
+                (
+                    place_goals,
+                    (track_elapsed, detect_dominos, check_outcome)
+                        .chain()
+                        .run_if(in_state(GameState::Simulating)),
+                    color_goals,
+                )
+                    .chain(),
+            );
     }
 }
 
-fn setup_goal(
+fn place_goals(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    mut level: ResMut<Level>,
+    layout: Res<LevelLayout>,
+    mut goal_query: Query<(Entity, &Goal, &mut Transform)>,
 ) {
-    commands.spawn((
-        Name::new("Goal"),
-        Goal,
-        Collider::cylinder(GOAL_HALF_WIDTH, GOAL_HALF_WIDTH),
-        Sensor,
-        CollidingEntities::default(),
-        ActiveCollisionTypes::all(),
-        ActiveEvents::COLLISION_EVENTS,
-        Mesh3d(meshes.add(Cylinder::new(GOAL_HALF_WIDTH, GOAL_WIDTH))),
-        MeshMaterial3d(materials.add(DEFAULT_COLOR)),
-        Transform::from_translation(GOAL_START_POS),
-    ));
-    level.is_won = false
+    if !layout.is_changed() {
+        return;
+    }
+    let positions: Vec<Vec3> = layout.goal_positions().collect();
+    // Moving a goal in the editor only needs the existing entities repositioned.
+    if goal_query.iter().count() == positions.len() {
+        for (_, goal, mut transform) in goal_query.iter_mut() {
+            transform.translation = positions[goal.index];
+        }
+        return;
+    }
+
+    for (entity, _, _) in goal_query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (index, position) in positions.into_iter().enumerate() {
+        commands.spawn((
+            Name::new("Goal"),
+            Goal { index },
+            Collider::cylinder(GOAL_HALF_WIDTH, GOAL_HALF_WIDTH),
+            Sensor,
+            CollidingEntities::default(),
+            ActiveCollisionTypes::all(),
+            ActiveEvents::COLLISION_EVENTS,
+            Mesh3d(meshes.add(Cylinder::new(GOAL_HALF_WIDTH, GOAL_WIDTH))),
+            MeshMaterial3d(materials.add(DEFAULT_COLOR)),
+            Transform::from_translation(position),
+        ));
+    }
+}
+
+fn reset_progress(layout: Res<LevelLayout>, mut progress: ResMut<GoalProgress>) {
+    progress.elapsed = 0.;
+    progress.hits.clear();
+    progress.hits.resize(layout.goals.len(), GoalHit::default());
+}
+
+fn track_elapsed(time: Res<Time>, mut progress: ResMut<GoalProgress>) {
+    progress.elapsed += time.delta_secs();
 }
 
 fn detect_dominos(
-    query: Query<(&CollidingEntities, &mut MeshMaterial3d<StandardMaterial>), With<Goal>>,
+    query: Query<(&Goal, &CollidingEntities)>,
+    domino_query: Query<(&Topple, Has<LastInChain>), With<Domino>>,
+    mut progress: ResMut<GoalProgress>,
+) {
+    let elapsed = progress.elapsed;
+    for (goal, colliding) in query.iter() {
+        let Some(hit) = progress.hits.get_mut(goal.index) else {
+            continue;
+        };
+        // Only dominoes the chain knocked over in sequence count, from any chain.
+        let hits = colliding
+            .iter()
+            .filter_map(|e| domino_query.get(e).ok())
+            .filter(|(topple, _)| topple.in_sequence);
+        for (_, is_last) in hits {
+            hit.first.get_or_insert(elapsed);
+            if is_last {
+                hit.by_last_domino.get_or_insert(elapsed);
+            }
+        }
+    }
+}
+
+/// Ends the run once the level is won, or once every chain has come to rest short of it.
+fn check_outcome(
+    layout: Res<LevelLayout>,
+    progress: Res<GoalProgress>,
+    chains: Res<ChainProgress>,
+    level: Res<Level>,
+    levels: Option<Res<Levels>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if is_won(&layout.win, &progress.hits) {
+        let count = levels.map_or(0, |levels| levels.definitions.len());
+        next_state.set(if level.value + 1 >= count {
+            GameState::AllComplete
+        } else {
+            GameState::Won
+        });
+    } else if chains.has_stopped() {
+        next_state.set(GameState::Failed);
+    }
+}
+
+fn color_goals(
+    query: Query<(&Goal, &MeshMaterial3d<StandardMaterial>)>,
+    progress: Res<GoalProgress>,
     mut materials: ResMut<Assets<StandardMaterial>>,
-    sim: Res<CurrentSimulation>,
-    mut level: ResMut<Level>,
 ) {
-    for (colliding, material_handle) in query.iter() {
+    for (goal, material_handle) in query.iter() {
         if let Some(material) = materials.get_mut(material_handle) {
-            if colliding.is_empty() || sim.state != SimulationState::Physics {
-                material.base_color = DEFAULT_COLOR;
-                level.is_won = false
+            let is_hit = progress
+                .hits
+                .get(goal.index)
+                .is_some_and(|hit| hit.first.is_some());
+            material.base_color = if is_hit { WIN_COLOR } else { DEFAULT_COLOR };
+        }
+    }
+}
+
+/// Whether the goal hits so far satisfy every one of the level's win conditions.
+pub fn is_won(conditions: &[WinCondition], hits: &[GoalHit]) -> bool {
+    if hits.is_empty() {
+        return false;
+    }
+    let by_last = conditions.contains(&WinCondition::LastDomino);
+    let times: Vec<Option<f32>> = hits
+        .iter()
+        .map(|hit| {
+            if by_last {
+                hit.by_last_domino
             } else {
-                material.base_color = WIN_COLOR;
-                level.is_won = true;
+                hit.first
             }
-        }
+        })
+        .collect();
+
+    let in_order = conditions.contains(&WinCondition::InOrder);
+    let needs_all = in_order || conditions.contains(&WinCondition::AllGoals);
+    let finished_at = if needs_all {
+        times
+            .iter()
+            .try_fold(0f32, |latest, time| time.map(|time| latest.max(time)))
+    } else {
+        times.iter().flatten().copied().reduce(f32::min)
+    };
+    let Some(finished_at) = finished_at else {
+        return false;
+    };
+
+    if in_order && !times.is_sorted_by(|a, b| a <= b) {
+        return false;
+    }
+    conditions.iter().all(|condition| match condition {
+        WinCondition::WithinSeconds(seconds) => finished_at <= *seconds,
+        _ => true,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use WinCondition::*;
+
+    /// Goals hit at these times by any domino, or not at all.
+    fn hits(times: &[Option<f32>]) -> Vec<GoalHit> {
+        times
+            .iter()
+            .map(|time| GoalHit {
+                first: *time,
+                by_last_domino: None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn any_goal_wins_by_default() {
+        assert!(is_won(&[], &hits(&[None, Some(2.)])));
+        assert!(!is_won(&[], &hits(&[None, None])));
+        assert!(!is_won(&[], &[]));
+    }
+
+    #[test]
+    fn all_goals_must_be_hit() {
+        assert!(!is_won(&[AllGoals], &hits(&[Some(1.), None])));
+        assert!(is_won(&[AllGoals], &hits(&[Some(3.), Some(1.)])));
+    }
+
+    #[test]
+    fn goals_must_be_hit_in_order() {
+        assert!(is_won(&[InOrder], &hits(&[Some(1.), Some(2.), Some(2.)])));
+        assert!(!is_won(&[InOrder], &hits(&[Some(2.), Some(1.)])));
+        assert!(!is_won(&[InOrder], &hits(&[Some(1.), None])));
+    }
+
+    #[test]
+    fn late_hits_dont_count() {
+        assert!(is_won(&[WithinSeconds(5.)], &hits(&[Some(5.)])));
+        assert!(!is_won(&[WithinSeconds(5.)], &hits(&[Some(5.5)])));
+        // With every goal needed, it's the last hit that has to be in time.
+        let late = hits(&[Some(1.), Some(6.)]);
+        assert!(is_won(&[WithinSeconds(5.)], &late));
+        assert!(!is_won(&[AllGoals, WithinSeconds(5.)], &late));
+    }
+
+    #[test]
+    fn only_the_last_domino_counts() {
+        let mut hits = hits(&[Some(1.)]);
+        assert!(!is_won(&[LastDomino], &hits));
+        hits[0].by_last_domino = Some(3.);
+        assert!(is_won(&[LastDomino], &hits));
+        assert!(!is_won(&[LastDomino, WithinSeconds(2.)], &hits));
     }
 }
@@ -1,57 +1,116 @@
-use bevy::prelude::*;
-use bevy_rapier3d::prelude::Collider;
+use bevy::{asset::RecursiveDependencyLoadState, prelude::*};
+use bevy_rapier3d::prelude::{ActiveCollisionTypes, ActiveEvents, Collider, CollidingEntities};
 // use bevy_simple_subsecond_system::hot;
 
 use crate::{
-    curve::{self, ControlPoints, CurrentSimulation},
-    domino::{self, Domino},
-    environment::{self, FLOOR_HALF_SIZE},
+    bindings::{Action, ActionState},
+    domino::Domino,
+    game_state::GameState,
+    level_asset::{
+        LEVEL_INDEX_PATH, LevelDefinition, LevelDefinitionLoader, LevelIndex, LevelIndexLoader,
+        WallDefinition,
+    },
 };
 
-pub const WALL_LENGTH_LONG: f32 = environment::FLOOR_LENGTH * 0.75;
-pub const WALL_LENGTH_SHORT: f32 = environment::FLOOR_LENGTH * 0.4;
-pub const WALL_THICKNESS: f32 = domino::DOMINO_DISTANCE;
-pub const WALL_HEIGHT: f32 = domino::DOMINO_SIZE.y * 0.9;
 pub const WALL_COLOR: Color = Color::srgb(0.3, 0.2, 0.1);
-pub const ROTATION_90Y: Quat = Quat::from_array([0., 0.7071068, 0., 0.7071068]);
+
+/// Entities left over from the previous level when a new one is spawned.
+type LevelEntity = Or<(With<Wall>, With<Domino>)>;
 
 #[derive(Resource, Default)]
 pub struct Level {
-    pub value: u32,
-    pub is_won: bool,
+    /// Index into [`Levels`]. Anything past the last level is free mode.
+    pub value: usize,
+}
+
+/// Every level of the campaign, in play order, once the level files have loaded.
+#[derive(Resource, Default)]
+pub struct Levels {
+    pub definitions: Vec<LevelDefinition>,
 }
 
+/// The layout of the level currently being played.
+/// Only written when a level is spawned, so other plugins can react to it changing.
+#[derive(Resource, Default, Deref)]
+pub struct LevelLayout(pub LevelDefinition);
+
+#[derive(Resource)]
+struct LevelIndexHandle(Handle<LevelIndex>);
+
 #[derive(Component)]
-pub struct Wall;
+pub struct Wall {
+    /// Length, height and thickness of the wall.
+    pub size: Vec3,
+}
 
 pub struct LevelPlugin;
 
 impl Plugin for LevelPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Startup, initiate_level)
-            .add_systems(Update, handle_next_level);
+        app.init_asset::<LevelDefinition>()
+            .init_asset::<LevelIndex>()
+            .init_asset_loader::<LevelDefinitionLoader>()
+            .init_asset_loader::<LevelIndexLoader>()
+            .insert_resource(LevelLayout::default())
+            .add_systems(Startup, load_levels)
+            .add_systems(OnEnter(GameState::LevelIntro), initiate_level)
+            .add_systems(
+                Update,
+                (
+                    collect_levels.run_if(in_state(GameState::Loading)),
+                    handle_next_level
+                        .run_if(in_state(GameState::Won).or(in_state(GameState::AllComplete))),
+                ),
+            );
+    }
+}
+
+fn load_levels(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(LevelIndexHandle(asset_server.load(LEVEL_INDEX_PATH)));
+}
+
+fn collect_levels(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    index_handle: Res<LevelIndexHandle>,
+    indices: Res<Assets<LevelIndex>>,
+    definitions: Res<Assets<LevelDefinition>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    match asset_server.recursive_dependency_load_state(&index_handle.0) {
+        RecursiveDependencyLoadState::Loaded => {
+            let Some(index) = indices.get(&index_handle.0) else {
+                return;
+            };
+            let definitions = index
+                .levels
+                .iter()
+                .filter_map(|handle| definitions.get(handle))
+                .cloned()
+                .collect();
+            commands.insert_resource(Levels { definitions });
+            next_state.set(GameState::MainMenu);
+        }
+        RecursiveDependencyLoadState::Failed(err) => {
+            error!("Failed to load levels, falling back to free mode: {err}");
+            commands.insert_resource(Levels::default());
+            next_state.set(GameState::MainMenu);
+        }
+        _ => {}
     }
 }
 
 // #[hot]
 fn handle_next_level(
-    mut commands: Commands,
-    marker_query: Query<Entity, With<Domino>>,
-    wall_query: Query<Entity, With<Wall>>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+    actions: Res<ActionState>,
     mut level: ResMut<Level>,
-    control_points: ResMut<ControlPoints>,
-    meshes: ResMut<Assets<Mesh>>,
-    materials: ResMut<Assets<StandardMaterial>>,
-    sim: ResMut<CurrentSimulation>,
+    levels: Res<Levels>,
+    mut next_state: ResMut<NextState<GameState>>,
 ) {
-    if level.is_won && keyboard.just_pressed(KeyCode::KeyN) {
-        curve::despawn_entities(&mut commands, marker_query);
-        curve::despawn_entities(&mut commands, wall_query);
-        curve::clear_curve(control_points, sim);
-        level.is_won = false;
-        level.value += 1;
-        initiate_level(commands, meshes, materials, level.into());
+    if actions.just_pressed(Action::NextLevel) {
+        // Stay in free mode once every level has been beaten.
+        level.value = (level.value + 1).min(levels.definitions.len());
+        next_state.set(GameState::LevelIntro);
     }
 }
 
@@ -61,191 +120,43 @@ fn initiate_level(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     level: Res<Level>,
+    levels: Res<Levels>,
+    cleanup_query: Query<Entity, LevelEntity>,
+    mut layout: ResMut<LevelLayout>,
 ) {
-    let y_pos = environment::FLOOR_HALF_SIZE.y + WALL_HEIGHT * 0.5;
-    match level.value {
-        // There's probably a better way to do this but I'm running out of time
-        0 => {}
-        1 => {
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    FLOOR_HALF_SIZE.x * 0.5 - WALL_THICKNESS * 0.5,
-                    y_pos,
-                    -FLOOR_HALF_SIZE.z + WALL_LENGTH_LONG * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    -FLOOR_HALF_SIZE.x + WALL_LENGTH_LONG * 0.5,
-                    y_pos,
-                    FLOOR_HALF_SIZE.z * 0.5 - WALL_THICKNESS * 0.5,
-                ),
-            ));
-        }
-        2 => {
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    FLOOR_HALF_SIZE.x - WALL_LENGTH_LONG * 0.5,
-                    y_pos,
-                    -FLOOR_HALF_SIZE.z * 0.33,
-                ),
-            ));
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    -FLOOR_HALF_SIZE.x + WALL_LENGTH_LONG * 0.5,
-                    y_pos,
-                    FLOOR_HALF_SIZE.z * 0.33,
-                ),
-            ));
-        }
-        3 => {
-            // Long Walls
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    -FLOOR_HALF_SIZE.x * 0.66,
-                    y_pos,
-                    FLOOR_HALF_SIZE.z - WALL_LENGTH_LONG * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    FLOOR_HALF_SIZE.x * 0.66,
-                    y_pos,
-                    -FLOOR_HALF_SIZE.z + WALL_LENGTH_LONG * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-
-            // Middle Wall
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_LONG, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_LONG * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(0., y_pos, 0.).with_rotation(ROTATION_90Y),
-            ));
-
-            // Short Walls
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_SHORT, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_SHORT * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    FLOOR_HALF_SIZE.x * 0.33,
-                    y_pos,
-                    -FLOOR_HALF_SIZE.z + WALL_LENGTH_SHORT * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_SHORT, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_SHORT * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    FLOOR_HALF_SIZE.x * 0.33,
-                    y_pos,
-                    FLOOR_HALF_SIZE.z - WALL_LENGTH_SHORT * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_SHORT, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_SHORT * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    -FLOOR_HALF_SIZE.x * 0.33,
-                    y_pos,
-                    -FLOOR_HALF_SIZE.z + WALL_LENGTH_SHORT * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-            commands.spawn((
-                Wall,
-                Mesh3d(meshes.add(Cuboid::new(WALL_LENGTH_SHORT, WALL_HEIGHT, WALL_THICKNESS))),
-                Collider::cuboid(
-                    WALL_LENGTH_SHORT * 0.5,
-                    WALL_HEIGHT * 0.5,
-                    WALL_THICKNESS * 0.5,
-                ),
-                MeshMaterial3d(materials.add(WALL_COLOR)),
-                Transform::from_xyz(
-                    -FLOOR_HALF_SIZE.x * 0.33,
-                    y_pos,
-                    FLOOR_HALF_SIZE.z - WALL_LENGTH_SHORT * 0.5,
-                )
-                .with_rotation(ROTATION_90Y),
-            ));
-        }
-        _ => {}
+    for entity in cleanup_query.iter() {
+        commands.entity(entity).despawn();
     }
+
+    let definition = levels
+        .definitions
+        .get(level.value)
+        .cloned()
+        .unwrap_or_default();
+    for wall in &definition.walls {
+        spawn_wall(&mut commands, &mut meshes, &mut materials, wall);
+    }
+
+    layout.0 = definition;
+}
+
+pub fn spawn_wall(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<StandardMaterial>,
+    wall: &WallDefinition,
+) -> Entity {
+    commands
+        .spawn((
+            Name::new("Wall"),
+            Wall { size: wall.size },
+            Mesh3d(meshes.add(Cuboid::from_size(wall.size))),
+            Collider::cuboid(wall.size.x * 0.5, wall.size.y * 0.5, wall.size.z * 0.5),
+            CollidingEntities::default(),
+            ActiveCollisionTypes::all(),
+            ActiveEvents::COLLISION_EVENTS,
+            MeshMaterial3d(materials.add(WALL_COLOR)),
+            wall.transform(),
+        ))
+        .id()
 }
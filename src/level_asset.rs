@@ -0,0 +1,403 @@
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    domino,
+    environment::{self, FLOOR_HALF_SIZE},
+    goal,
+    physics_profile::DominoPhysicsOverrides,
+};
+
+pub const LEVEL_INDEX_PATH: &str = "levels/campaign.levels.ron";
+
+/// A single level as described by a `.level.ron` file.
+/// Positions are given on the floor plane as `(x, z)`.
+#[derive(Asset, TypePath, Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct LevelDefinition {
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub walls: Vec<WallDefinition>,
+    #[serde(default = "default_goals")]
+    pub goals: Vec<Vec2>,
+    /// Extra rules on top of reaching a goal. All of them must hold to win.
+    #[serde(default)]
+    pub win: Vec<WinCondition>,
+    #[serde(default = "default_pusher")]
+    pub pusher: Vec2,
+    /// How the pusher moves once the run starts.
+    #[serde(default)]
+    pub push: PushDefinition,
+    /// Maximum number of dominoes the player may place, if limited.
+    #[serde(default)]
+    pub max_dominoes: Option<u32>,
+    /// Score needed for each star, lowest first. Uses [`crate::score::DEFAULT_STARS`] if empty.
+    #[serde(default)]
+    pub stars: Vec<u32>,
+    /// Pressure plates that set off mechanisms when a domino falls on them.
+    #[serde(default)]
+    pub switches: Vec<SwitchDefinition>,
+    /// Moving parts of the level, linked to switches by their position in this list.
+    #[serde(default)]
+    pub mechanisms: Vec<MechanismDefinition>,
+    /// Raised ground that dominoes can stand on, on top of the floor.
+    #[serde(default)]
+    pub terrain: Vec<TerrainDefinition>,
+    /// Changes to the domino physics profile for this level only.
+    #[serde(default)]
+    pub physics: DominoPhysicsOverrides,
+    /// Asset path the level was loaded from. Unlike the name it is unique, so saves and
+    /// replays are kept by it. Empty for free mode.
+    #[serde(skip)]
+    pub path: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum WinCondition {
+    /// Every goal must be hit, not just one.
+    AllGoals,
+    /// Every goal must be hit, in the order they are listed.
+    InOrder,
+    /// The goals must be hit within this many seconds of the push.
+    WithinSeconds(f32),
+    /// Only the last domino of a chain counts as hitting a goal.
+    LastDomino,
+}
+
+/// How the pusher moves at normal strength. It travels in a straight line at `speed` until
+/// it has gone `distance`, and gives the first domino it touches an extra shove of `impulse`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(default)]
+pub struct PushDefinition {
+    /// Heading on the floor plane in degrees, measured like wall rotation from the x axis.
+    /// Toward the first domino if left out.
+    pub direction: Option<f32>,
+    /// Units per second.
+    pub speed: f32,
+    pub distance: f32,
+    pub impulse: f32,
+}
+
+impl Default for PushDefinition {
+    fn default() -> Self {
+        Self {
+            direction: None,
+            speed: 1.,
+            distance: domino::DOMINO_DISTANCE * 1.75,
+            impulse: 0.,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct WallDefinition {
+    pub position: Vec2,
+    /// Length, height and thickness of the wall.
+    pub size: Vec3,
+    /// Rotation around the vertical axis in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SwitchDefinition {
+    pub position: Vec2,
+    /// Indices into the level's mechanisms that this switch sets off.
+    pub targets: Vec<usize>,
+}
+
+/// A wall-like block that moves once one of its switches is pressed.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MechanismDefinition {
+    pub kind: MechanismKind,
+    pub position: Vec2,
+    /// Length, height and thickness of the block.
+    pub size: Vec3,
+    /// Rotation around the vertical axis in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum MechanismKind {
+    /// Sinks into the floor, opening the way.
+    Door,
+    /// Swings up about its near end by this many degrees.
+    Ramp { angle: f32 },
+    /// Slides back and forth between its position and `to`, in units per second.
+    Platform { to: Vec2, speed: f32 },
+}
+
+/// A raised piece of ground. Its footprint is `size` long along its rotated x axis and
+/// `size.y` wide, and it rises `height` above the floor.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct TerrainDefinition {
+    pub kind: TerrainKind,
+    pub position: Vec2,
+    pub size: Vec2,
+    pub height: f32,
+    /// Rotation around the vertical axis in degrees.
+    #[serde(default)]
+    pub rotation: f32,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TerrainKind {
+    /// Flat on top.
+    Platform,
+    /// Climbs from the floor to its full height in equal steps along its length.
+    Stairs { steps: u32 },
+    /// Slopes evenly from the floor to its full height along its length.
+    Ramp,
+}
+
+/// The top of the ground at a point on the floor plane.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Surface {
+    pub height: f32,
+    pub normal: Vec3,
+}
+
+/// The ordered list of levels that make up the campaign.
+#[derive(Asset, TypePath, Debug)]
+pub struct LevelIndex {
+    #[dependency]
+    pub levels: Vec<Handle<LevelDefinition>>,
+}
+
+/// The campaign index as written on disk, before its level paths are resolved.
+#[derive(Deserialize)]
+pub struct LevelIndexFile {
+    pub levels: Vec<String>,
+}
+
+impl Default for LevelDefinition {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            walls: Vec::new(),
+            goals: default_goals(),
+            win: Vec::new(),
+            pusher: default_pusher(),
+            push: PushDefinition::default(),
+            max_dominoes: None,
+            stars: Vec::new(),
+            switches: Vec::new(),
+            mechanisms: Vec::new(),
+            terrain: Vec::new(),
+            physics: DominoPhysicsOverrides::default(),
+            path: String::new(),
+        }
+    }
+}
+
+/// Save key of the level that isn't loaded from a file.
+pub const FREE_MODE_KEY: &str = "free mode";
+
+impl LevelDefinition {
+    /// What the level's progress and replays are kept under.
+    pub fn save_key(&self) -> &str {
+        if self.path.is_empty() {
+            FREE_MODE_KEY
+        } else {
+            &self.path
+        }
+    }
+
+    pub fn goal_positions(&self) -> impl Iterator<Item = Vec3> + '_ {
+        self.goals
+            .iter()
+            .map(|goal| vec3(goal.x, goal::GOAL_START_POS.y + self.rise_at(*goal), goal.y))
+    }
+
+    pub fn pusher_position(&self) -> Vec3 {
+        vec3(
+            self.pusher.x,
+            environment::PUSHER_START_POS.y + self.rise_at(self.pusher),
+            self.pusher.y,
+        )
+    }
+
+    /// The first control point of the domino path, directly under the pusher.
+    pub fn control_start(&self) -> Vec3 {
+        let height = self.surface_at(self.pusher).height;
+        vec3(self.pusher.x, domino::standing_y(height), self.pusher.y)
+    }
+
+    /// The highest ground at a point, whether the floor or a piece of terrain.
+    pub fn surface_at(&self, point: Vec2) -> Surface {
+        let floor = Surface {
+            height: FLOOR_HALF_SIZE.y,
+            normal: Vec3::Y,
+        };
+        self.terrain
+            .iter()
+            .filter_map(|terrain| terrain.surface_at(point))
+            .fold(floor, |highest, surface| {
+                if surface.height > highest.height {
+                    surface
+                } else {
+                    highest
+                }
+            })
+    }
+
+    /// How far the ground at a point is raised above the floor.
+    fn rise_at(&self, point: Vec2) -> f32 {
+        self.surface_at(point).height - FLOOR_HALF_SIZE.y
+    }
+}
+
+impl TerrainDefinition {
+    pub fn rotation(&self) -> Quat {
+        Quat::from_rotation_y(self.rotation.to_radians())
+    }
+
+    /// The top of this piece at a point, if the point is over it.
+    pub fn surface_at(&self, point: Vec2) -> Option<Surface> {
+        let rotation = self.rotation();
+        let offset = point - self.position;
+        let local = rotation.inverse() * vec3(offset.x, 0., offset.y);
+        let half_size = self.size * 0.5;
+        if local.x.abs() > half_size.x || local.z.abs() > half_size.y {
+            return None;
+        }
+        // How far along its length the point is, from 0 at the low end to 1 at the high end.
+        let along = (local.x + half_size.x) / self.size.x;
+        let (rise, normal) = match self.kind {
+            TerrainKind::Platform => (self.height, Vec3::Y),
+            TerrainKind::Stairs { steps } => {
+                let steps = steps.max(1);
+                let step = ((along * steps as f32) as u32).min(steps - 1);
+                (self.height * (step + 1) as f32 / steps as f32, Vec3::Y)
+            }
+            TerrainKind::Ramp => {
+                let normal = rotation * vec3(-self.height, self.size.x, 0.).normalize();
+                (self.height * along, normal)
+            }
+        };
+        Some(Surface {
+            height: FLOOR_HALF_SIZE.y + rise,
+            normal,
+        })
+    }
+}
+
+impl WinCondition {
+    pub fn describe(&self) -> String {
+        match self {
+            WinCondition::AllGoals => "Hit every goal.".into(),
+            WinCondition::InOrder => "Hit the goals in order.".into(),
+            WinCondition::WithinSeconds(seconds) => format!("Within {seconds} seconds."),
+            WinCondition::LastDomino => "Only the last domino counts.".into(),
+        }
+    }
+}
+
+impl WallDefinition {
+    pub fn from_transform(transform: &Transform, size: Vec3) -> Self {
+        let (yaw, _, _) = transform.rotation.to_euler(EulerRot::YXZ);
+        Self {
+            position: transform.translation.xz(),
+            size,
+            rotation: yaw.to_degrees(),
+        }
+    }
+
+    pub fn transform(&self) -> Transform {
+        floor_transform(self.position, self.size, self.rotation)
+    }
+}
+
+impl MechanismDefinition {
+    /// Where the mechanism rests before it is set off.
+    pub fn transform(&self) -> Transform {
+        floor_transform(self.position, self.size, self.rotation)
+    }
+}
+
+/// A block of `size` standing on the floor at `position`, turned `rotation` degrees.
+fn floor_transform(position: Vec2, size: Vec3, rotation: f32) -> Transform {
+    Transform::from_xyz(position.x, FLOOR_HALF_SIZE.y + size.y * 0.5, position.y)
+        .with_rotation(Quat::from_rotation_y(rotation.to_radians()))
+}
+
+fn default_goals() -> Vec<Vec2> {
+    vec![goal::GOAL_START_POS.xz()]
+}
+
+fn default_pusher() -> Vec2 {
+    environment::PUSHER_START_POS.xz()
+}
+
+#[derive(Debug, Error)]
+pub enum LevelLoaderError {
+    #[error("Could not read level file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse level file: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+    #[error("Invalid level path: {0}")]
+    Path(#[from] bevy::asset::ParseAssetPathError),
+}
+
+#[derive(Default)]
+pub struct LevelDefinitionLoader;
+
+impl AssetLoader for LevelDefinitionLoader {
+    type Asset = LevelDefinition;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(LevelDefinition {
+            path: load_context.asset_path().to_string(),
+            ..ron::de::from_bytes(&bytes)?
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["level.ron"]
+    }
+}
+
+#[derive(Default)]
+pub struct LevelIndexLoader;
+
+impl AssetLoader for LevelIndexLoader {
+    type Asset = LevelIndex;
+    type Settings = ();
+    type Error = LevelLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        let file = ron::de::from_bytes::<LevelIndexFile>(&bytes)?;
+        // Level paths are relative to the index file.
+        let mut levels = Vec::with_capacity(file.levels.len());
+        for path in &file.levels {
+            let path = load_context.asset_path().resolve_embed(path)?;
+            levels.push(load_context.load(path));
+        }
+        Ok(LevelIndex { levels })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["levels.ron"]
+    }
+}
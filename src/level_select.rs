@@ -0,0 +1,180 @@
+use bevy::prelude::*;
+
+use crate::{
+    bindings::{Action, ActionState, Bindings},
+    game_state::GameState,
+    level::{Level, Levels},
+    save::SaveData,
+    score,
+};
+
+const PANEL_COLOR: Color = Color::srgba(0., 0., 0., 0.85);
+const LOCKED_COLOR: Color = Color::srgb(0.25, 0.25, 0.25);
+const UNLOCKED_COLOR: Color = Color::srgb(0.2, 0.35, 0.6);
+const COMPLETED_COLOR: Color = Color::srgb(0., 0.5, 0.5);
+const CURRENT_BORDER_COLOR: Color = Color::srgb(1., 1., 0.);
+
+#[derive(Default, PartialEq, Eq, Debug, Clone, Copy)]
+pub enum LevelStatus {
+    #[default]
+    Locked,
+    Unlocked,
+    Completed,
+}
+
+/// A button that jumps to a level. One past the last level is free mode.
+#[derive(Component)]
+struct LevelButton(usize);
+
+pub struct LevelSelectPlugin;
+
+impl Plugin for LevelSelectPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::MainMenu), spawn_menu)
+            .add_systems(
+                Update,
+                (
+                    open_level_select.run_if(in_state(GameState::Drawing)),
+                    (close_level_select, select_level).run_if(in_state(GameState::MainMenu)),
+                ),
+            );
+    }
+}
+
+/// The first level is always open, and each completed level unlocks the next.
+/// Free mode opens once every level is complete.
+pub fn level_status(index: usize, levels: &Levels, save: &SaveData) -> LevelStatus {
+    let is_complete = |index: usize| {
+        levels
+            .definitions
+            .get(index)
+            .and_then(|definition| save.level(definition.save_key()))
+            .is_some_and(|saved| saved.is_complete)
+    };
+    if index < levels.definitions.len() && is_complete(index) {
+        LevelStatus::Completed
+    } else if index == 0 || is_complete(index - 1) {
+        LevelStatus::Unlocked
+    } else {
+        LevelStatus::Locked
+    }
+}
+
+fn open_level_select(actions: Res<ActionState>, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.just_pressed(Action::LevelSelect) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+/// Goes back to the current level.
+fn close_level_select(actions: Res<ActionState>, mut next_state: ResMut<NextState<GameState>>) {
+    if actions.just_pressed(Action::LevelSelect) {
+        next_state.set(GameState::LevelIntro);
+    }
+}
+
+fn spawn_menu(
+    mut commands: Commands,
+    levels: Res<Levels>,
+    level: Res<Level>,
+    save: Res<SaveData>,
+    bindings: Res<Bindings>,
+) {
+    let menu = commands
+        .spawn((
+            StateScoped(GameState::MainMenu),
+            Node {
+                position_type: PositionType::Absolute,
+                width: Val::Percent(100.),
+                height: Val::Percent(100.),
+                flex_direction: FlexDirection::Column,
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                row_gap: Val::Px(8.),
+                ..default()
+            },
+            BackgroundColor(PANEL_COLOR),
+            GlobalZIndex(1),
+        ))
+        .id();
+    commands.spawn((
+        Text::new(format!(
+            "Select a level\n\n{} to close",
+            bindings.describe(Action::LevelSelect)
+        )),
+        TextLayout::new_with_justify(JustifyText::Center),
+        Node {
+            margin: UiRect::bottom(Val::Px(12.)),
+            ..default()
+        },
+        ChildOf(menu),
+    ));
+
+    for index in 0..=levels.definitions.len() {
+        let status = level_status(index, &levels, &save);
+        let label = match levels.definitions.get(index) {
+            Some(definition) => {
+                let saved = save.level(definition.save_key());
+                let mut label = format!("{}. {}", index + 1, definition.name);
+                match status {
+                    LevelStatus::Locked => label.push_str("  (locked)"),
+                    LevelStatus::Unlocked => {}
+                    LevelStatus::Completed => {
+                        label.push_str("  - Replay");
+                        if let Some(saved) = saved {
+                            let max = score::max_stars(definition);
+                            label.push_str(&format!("  {}/{max} stars", saved.stars));
+                        }
+                        if let Some(seconds) = saved.and_then(|saved| saved.best_seconds) {
+                            label.push_str(&format!("  best {seconds:.1}s"));
+                        }
+                        if let Some(dominoes) = saved.and_then(|saved| saved.fewest_dominoes) {
+                            label.push_str(&format!(", {dominoes} dominoes"));
+                        }
+                    }
+                }
+                label
+            }
+            None if status == LevelStatus::Locked => "Free Mode  (locked)".into(),
+            None => "Free Mode".into(),
+        };
+        let color = match status {
+            LevelStatus::Locked => LOCKED_COLOR,
+            LevelStatus::Unlocked => UNLOCKED_COLOR,
+            LevelStatus::Completed => COMPLETED_COLOR,
+        };
+        let border = if index == level.value {
+            CURRENT_BORDER_COLOR
+        } else {
+            color
+        };
+        let mut button = commands.spawn((
+            Node {
+                width: Val::Px(600.),
+                padding: UiRect::axes(Val::Px(16.), Val::Px(8.)),
+                border: UiRect::all(Val::Px(2.)),
+                ..default()
+            },
+            BackgroundColor(color),
+            BorderColor(border),
+            ChildOf(menu),
+            children![Text::new(label)],
+        ));
+        if status != LevelStatus::Locked {
+            button.insert((Button, LevelButton(index)));
+        }
+    }
+}
+
+fn select_level(
+    button_query: Query<(&Interaction, &LevelButton), Changed<Interaction>>,
+    mut level: ResMut<Level>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    for (interaction, button) in button_query.iter() {
+        if *interaction == Interaction::Pressed {
+            level.value = button.0;
+            next_state.set(GameState::LevelIntro);
+        }
+    }
+}
@@ -0,0 +1,27 @@
+pub mod analysis;
+pub mod bindings;
+pub mod camera;
+pub mod cursor;
+pub mod curve;
+pub mod curve_history;
+pub mod domino;
+pub mod editor;
+pub mod environment;
+pub mod floor;
+pub mod game_state;
+pub mod goal;
+pub mod level;
+pub mod level_asset;
+pub mod level_select;
+pub mod mechanism;
+pub mod path_tool;
+pub mod physics_profile;
+pub mod placement;
+pub mod pusher;
+pub mod replay;
+pub mod save;
+pub mod score;
+pub mod terrain;
+pub mod time_control;
+pub mod ui;
+pub mod verify;
@@ -1,28 +1,21 @@
-mod camera;
-mod cursor;
-mod curve;
-mod domino;
-mod environment;
-mod floor;
-mod goal;
-mod level;
-mod pusher;
-mod ui;
-
-use crate::camera::*;
-use crate::cursor::*;
-use crate::curve::*;
-use crate::environment::*;
-use crate::goal::*;
 // use bevy_simple_subsecond_system::SimpleSubsecondPlugin;
 
 use bevy::prelude::*;
+use bevy_jam6_game::{
+    bindings::BindingsPlugin, camera::*, cursor::*, curve::*, domino::DominoPlugin,
+    editor::EditorPlugin, environment::*, game_state::GameStatePlugin, goal::*, level::LevelPlugin,
+    level_select::LevelSelectPlugin, mechanism::MechanismPlugin,
+    physics_profile::PhysicsProfilePlugin, pusher::PusherPlugin, replay::ReplayPlugin,
+    save::SavePlugin, score::ScorePlugin, terrain::TerrainPlugin, time_control::TimeControlPlugin,
+    ui::UIPlugin, verify,
+};
 use bevy_rapier3d::prelude::*;
-use domino::DominoPlugin;
-use level::LevelPlugin;
-use pusher::PusherPlugin;
-use ui::UIPlugin;
-fn main() {
+
+fn main() -> AppExit {
+    if std::env::args().any(|arg| arg == verify::VERIFY_ARG) {
+        return verify::verify_campaign();
+    }
+
     App::new()
         .add_plugins(
             DefaultPlugins
@@ -36,16 +29,27 @@ fn main() {
                     ..default()
                 }),
         )
-        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default())
+        .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
+        .add_plugins(TimeControlPlugin)
         // .add_plugins(SimpleSubsecondPlugin::default())
+        .add_plugins(GameStatePlugin)
+        .add_plugins(BindingsPlugin)
         .add_plugins(EnvironmentPlugin)
         .add_plugins(CameraPlugin)
         .add_plugins(DominoPlugin)
+        .add_plugins(PhysicsProfilePlugin)
         .add_plugins(PusherPlugin)
         .add_plugins(CursorPlugin)
         .add_plugins(CurvePlugin)
         .add_plugins(UIPlugin)
         .add_plugins(GoalPlugin)
+        .add_plugins(MechanismPlugin)
+        .add_plugins(TerrainPlugin)
         .add_plugins(LevelPlugin)
-        .run();
+        .add_plugins(EditorPlugin)
+        .add_plugins(SavePlugin)
+        .add_plugins(ScorePlugin)
+        .add_plugins(ReplayPlugin)
+        .add_plugins(LevelSelectPlugin)
+        .run()
 }
@@ -0,0 +1,301 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    domino::{self, Domino, DominoState, Topple, TriggerFallen},
+    environment,
+    game_state::{self, GameState},
+    level::LevelLayout,
+    level_asset::{MechanismDefinition, MechanismKind},
+};
+
+/// Thinner than the gap under a standing domino, so a path can be laid over a switch.
+pub const SWITCH_SIZE: Vec3 = Vec3::new(2., domino::DOMINO_Y_OFFSET * 0.8, 2.);
+const SWITCH_COLOR: Color = Color::srgb(0.8, 0.2, 0.2);
+const PRESSED_SWITCH_COLOR: Color = Color::srgb(0.2, 0.8, 0.2);
+const MECHANISM_COLOR: Color = Color::srgb(0.45, 0.45, 0.55);
+/// How fast a door sinks into the floor, in units per second.
+const DOOR_SPEED: f32 = 4.;
+/// How fast a ramp swings up, in degrees per second.
+const RAMP_SPEED: f32 = 90.;
+/// How far along the floor a fallen trigger domino reaches for a switch: about a domino's
+/// height past the switch's edge, so it has to fall on or beside it.
+pub const TRIGGER_RANGE: f32 = SWITCH_SIZE.x * 0.5 + domino::DOMINO_SIZE.y;
+
+/// Entities spawned from the level's switches and mechanisms.
+type MechanismEntity = Or<(With<Switch>, With<Mechanism>)>;
+
+/// A pressure plate on the floor, pressed by the first domino to fall on it.
+#[derive(Component, Debug)]
+pub struct Switch {
+    /// Indices of the mechanisms this switch sets off.
+    pub targets: Vec<usize>,
+    pub is_pressed: bool,
+}
+
+/// A door, ramp or platform that moves once one of its switches is pressed.
+#[derive(Component, Debug)]
+pub struct Mechanism {
+    /// Position of this mechanism in the level's mechanism list.
+    pub index: usize,
+    pub definition: MechanismDefinition,
+    /// Seconds since it was set off, if it has been.
+    pub active_for: Option<f32>,
+}
+
+pub struct MechanismPlugin;
+
+impl Plugin for MechanismPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(OnEnter(GameState::Drawing), reset_mechanisms)
+            .add_systems(FixedUpdate, move_mechanisms.run_if(game_state::after_push))
+            .add_systems(
+                Update,
+                (
+                    spawn_mechanisms,
+                    (
+                        press_switches,
+                        press_triggered_switches,
+                        activate_mechanisms,
+                    )
+                        .chain()
+                        .run_if(in_state(GameState::Simulating)),
+                    color_switches,
+                )
+                    .chain(),
+            );
+    }
+}
+
+/// Replaces the switches and mechanisms whenever a level is spawned or edited.
+fn spawn_mechanisms(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    layout: Res<LevelLayout>,
+    query: Query<Entity, MechanismEntity>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let half_size = SWITCH_SIZE * 0.5;
+    for switch in &layout.switches {
+        commands.spawn((
+            Name::new("Switch"),
+            Switch {
+                targets: switch.targets.clone(),
+                is_pressed: false,
+            },
+            Collider::cuboid(half_size.x, half_size.y, half_size.z),
+            Sensor,
+            CollidingEntities::default(),
+            ActiveCollisionTypes::all(),
+            ActiveEvents::COLLISION_EVENTS,
+            Mesh3d(meshes.add(Cuboid::from_size(SWITCH_SIZE))),
+            MeshMaterial3d(materials.add(SWITCH_COLOR)),
+            Transform::from_xyz(
+                switch.position.x,
+                environment::FLOOR_HALF_SIZE.y + half_size.y,
+                switch.position.y,
+            ),
+        ));
+    }
+
+    for (index, definition) in layout.mechanisms.iter().enumerate() {
+        let size = definition.size;
+        commands.spawn((
+            Name::new("Mechanism"),
+            Mechanism {
+                index,
+                definition: definition.clone(),
+                active_for: None,
+            },
+            RigidBody::KinematicPositionBased,
+            Collider::cuboid(size.x * 0.5, size.y * 0.5, size.z * 0.5),
+            Mesh3d(meshes.add(Cuboid::from_size(size))),
+            MeshMaterial3d(materials.add(MECHANISM_COLOR)),
+            definition.transform(),
+        ));
+    }
+}
+
+/// Puts every mechanism back where it started and releases the switches.
+fn reset_mechanisms(
+    mut switch_query: Query<&mut Switch>,
+    mut mechanism_query: Query<(&mut Mechanism, &mut Transform)>,
+) {
+    for mut switch in switch_query.iter_mut() {
+        switch.is_pressed = false;
+    }
+    for (mut mechanism, mut transform) in mechanism_query.iter_mut() {
+        mechanism.active_for = None;
+        *transform = mechanism.definition.transform();
+    }
+}
+
+/// Presses any switch a falling domino has landed on. Standing dominoes don't count,
+/// so a path may run over a switch.
+fn press_switches(
+    mut query: Query<(&mut Switch, &CollidingEntities)>,
+    domino_query: Query<&Topple, With<Domino>>,
+) {
+    for (mut switch, colliding) in query.iter_mut() {
+        if switch.is_pressed {
+            continue;
+        }
+        let is_hit = colliding
+            .iter()
+            .filter_map(|e| domino_query.get(e).ok())
+            .any(|topple| topple.state != DominoState::Standing);
+        if is_hit {
+            switch.is_pressed = true;
+        }
+    }
+}
+
+/// A fallen trigger domino presses the switch closest to it, if one is within [`TRIGGER_RANGE`].
+fn press_triggered_switches(
+    mut triggers: EventReader<TriggerFallen>,
+    domino_query: Query<&Transform, With<Domino>>,
+    mut query: Query<(&mut Switch, &Transform)>,
+) {
+    for trigger in triggers.read() {
+        let Ok(domino) = domino_query.get(trigger.domino) else {
+            continue;
+        };
+        let floor_distance = |transform: &Transform| {
+            (transform.translation - domino.translation)
+                .with_y(0.)
+                .length()
+        };
+        let closest = query
+            .iter_mut()
+            .map(|(switch, transform)| (switch, floor_distance(transform)))
+            .filter(|(_, distance)| *distance <= TRIGGER_RANGE)
+            .min_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((mut switch, _)) = closest {
+            switch.is_pressed = true;
+        }
+    }
+}
+
+fn activate_mechanisms(switch_query: Query<&Switch>, mut query: Query<&mut Mechanism>) {
+    for switch in switch_query.iter().filter(|switch| switch.is_pressed) {
+        for mut mechanism in query.iter_mut() {
+            if switch.targets.contains(&mechanism.index) && mechanism.active_for.is_none() {
+                mechanism.active_for = Some(0.);
+            }
+        }
+    }
+}
+
+fn move_mechanisms(time: Res<Time>, mut query: Query<(&mut Mechanism, &mut Transform)>) {
+    for (mut mechanism, mut transform) in query.iter_mut() {
+        let Some(active_for) = mechanism.active_for else {
+            continue;
+        };
+        let active_for = active_for + time.delta_secs();
+        mechanism.active_for = Some(active_for);
+        *transform = moved_transform(&mechanism.definition, active_for);
+    }
+}
+
+/// Where a mechanism is after being active for `seconds`.
+fn moved_transform(definition: &MechanismDefinition, seconds: f32) -> Transform {
+    let home = definition.transform();
+    let size = definition.size;
+    match definition.kind {
+        MechanismKind::Door => {
+            let depth = (DOOR_SPEED * seconds).min(size.y);
+            home.with_translation(home.translation - Vec3::Y * depth)
+        }
+        MechanismKind::Ramp { angle } => {
+            let angle = (RAMP_SPEED * seconds).min(angle.abs()) * angle.signum();
+            // Swing about the bottom of the near end, along the ramp's length.
+            let half_length = home.rotation * Vec3::X * size.x * 0.5;
+            let pivot = home.translation - half_length - Vec3::Y * size.y * 0.5;
+            let swing = Quat::from_axis_angle(home.rotation * Vec3::Z, angle.to_radians());
+            let offset = home.translation - pivot;
+            Transform::from_translation(pivot + swing * offset).with_rotation(swing * home.rotation)
+        }
+        MechanismKind::Platform { to, speed } => {
+            let to = vec3(to.x, home.translation.y, to.y);
+            let distance = home.translation.distance(to);
+            if distance <= f32::EPSILON {
+                return home;
+            }
+            // Back and forth, starting towards `to`.
+            let travelled = (speed * seconds) % (2. * distance);
+            let fraction = 1. - (travelled / distance - 1.).abs();
+            home.with_translation(home.translation.lerp(to, fraction))
+        }
+    }
+}
+
+fn color_switches(
+    query: Query<(&Switch, &MeshMaterial3d<StandardMaterial>), Changed<Switch>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+    for (switch, material_handle) in query.iter() {
+        if let Some(material) = materials.get_mut(material_handle) {
+            material.base_color = if switch.is_pressed {
+                PRESSED_SWITCH_COLOR
+            } else {
+                SWITCH_COLOR
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domino::DominoKind;
+
+    /// Drops a trigger domino at `position` with one switch at the origin, and says whether
+    /// the switch ended up pressed.
+    fn trigger_at(position: Vec3) -> bool {
+        let mut app = App::new();
+        app.add_event::<TriggerFallen>()
+            .add_systems(Update, press_triggered_switches);
+        let switch = app
+            .world_mut()
+            .spawn((
+                Switch {
+                    targets: vec![0],
+                    is_pressed: false,
+                },
+                Transform::default(),
+            ))
+            .id();
+        let domino = app
+            .world_mut()
+            .spawn((
+                Domino {
+                    chain: 0,
+                    index: 0,
+                    kind: DominoKind::Trigger,
+                },
+                Transform::from_translation(position),
+            ))
+            .id();
+        app.world_mut().send_event(TriggerFallen { domino });
+        app.update();
+        app.world().get::<Switch>(switch).unwrap().is_pressed
+    }
+
+    #[test]
+    fn trigger_beside_a_switch_presses_it() {
+        assert!(trigger_at(vec3(TRIGGER_RANGE - 0.5, 1., 0.)));
+    }
+
+    #[test]
+    fn trigger_out_of_range_presses_nothing() {
+        assert!(!trigger_at(vec3(TRIGGER_RANGE + 0.5, 0., 0.)));
+        assert!(!trigger_at(vec3(20., 0., -20.)));
+    }
+}
@@ -0,0 +1,192 @@
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// A freehand stroke is sampled when the cursor has moved at least this far.
+pub const STROKE_SAMPLE_DISTANCE: f32 = 0.25;
+/// How far a simplified stroke may stray from the one drawn.
+const STROKE_TOLERANCE: f32 = 0.35;
+/// A single cubic drifts visibly off a circle beyond a half turn.
+const MAX_ARC_SWEEP: f32 = PI;
+
+/// How clicks and drags build the path.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathTool {
+    /// Clicked points joined by a smooth spline.
+    #[default]
+    Points,
+    /// Hold and drag to draw, simplified into smooth points on release.
+    Freehand,
+    /// Clicked points joined by straight lines with sharp corners.
+    Straight,
+    /// Clicked points joined by arcs that carry on in the direction the path was going.
+    Arc,
+}
+
+impl PathTool {
+    pub fn next(self) -> Self {
+        match self {
+            PathTool::Points => PathTool::Freehand,
+            PathTool::Freehand => PathTool::Straight,
+            PathTool::Straight => PathTool::Arc,
+            PathTool::Arc => PathTool::Points,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            PathTool::Points => "points",
+            PathTool::Freehand => "freehand",
+            PathTool::Straight => "straight lines",
+            PathTool::Arc => "arcs",
+        }
+    }
+
+    /// The shape of the segments this tool adds.
+    pub fn shape(self) -> SegmentShape {
+        match self {
+            PathTool::Points | PathTool::Freehand => SegmentShape::Smooth,
+            PathTool::Straight => SegmentShape::Straight,
+            PathTool::Arc => SegmentShape::Arc,
+        }
+    }
+}
+
+/// The shape of a chain between two neighbouring control points.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentShape {
+    /// Part of a Catmull-Rom spline through the points either side.
+    #[default]
+    Smooth,
+    Straight,
+    /// A circular arc leaving its start in the direction the path was already going.
+    /// The first segment of a chain has no direction yet, so is straight instead,
+    /// and so is an arc that would bend more than a half turn.
+    Arc,
+}
+
+/// Joins control points into one curve, with a segment of the given shape between each
+/// neighbouring pair. Smooth segments meet straight lines and arcs without a corner.
+pub fn build_curve(points: &[Vec3], shapes: &[SegmentShape]) -> Option<CubicCurve<Vec3>> {
+    if points.len() < 2 {
+        return None;
+    }
+    let shape = |segment: usize| shapes.get(segment).copied().unwrap_or_default();
+    let last = points.len() - 1;
+    // Catmull-Rom tangents, mirroring the end points as Bevy's cardinal spline does.
+    let smooth_tangent = |index: usize| {
+        let before = if index == 0 {
+            points[0] * 2. - points[1]
+        } else {
+            points[index - 1]
+        };
+        let after = if index == last {
+            points[last] * 2. - points[last - 1]
+        } else {
+            points[index + 1]
+        };
+        (after - before) * 0.5
+    };
+
+    let mut segments = Vec::with_capacity(last);
+    // The shape of the segment before and the direction it ended in.
+    let mut before: Option<(SegmentShape, Vec3)> = None;
+    for segment in 0..last {
+        let (start, end) = (points[segment], points[segment + 1]);
+        let chord = end - start;
+        let (leaving, arriving) = match shape(segment) {
+            SegmentShape::Straight => (chord, chord),
+            SegmentShape::Arc => arc_tangents(chord, before.map(|(_, tangent)| tangent)),
+            SegmentShape::Smooth => {
+                let mut leaving = smooth_tangent(segment);
+                if let Some((shape, tangent)) = before
+                    && shape != SegmentShape::Smooth
+                {
+                    leaving = tangent.normalize_or_zero() * leaving.length();
+                }
+                let mut arriving = smooth_tangent(segment + 1);
+                if segment + 1 < last && shape(segment + 1) == SegmentShape::Straight {
+                    let next = points[segment + 2] - end;
+                    arriving = next.normalize_or_zero() * arriving.length();
+                }
+                (leaving, arriving)
+            }
+        };
+        segments.push(CubicSegment::new_bezier([
+            start,
+            start + leaving / 3.,
+            end - arriving / 3.,
+            end,
+        ]));
+        before = Some((shape(segment), arriving));
+    }
+    CubicCurve::from_segments(segments)
+}
+
+/// Tangents at both ends of an arc across `chord` that leaves in the direction `before`,
+/// falling back to a straight line where there is no such arc.
+fn arc_tangents(chord: Vec3, before: Option<Vec3>) -> (Vec3, Vec3) {
+    let flat = chord.xz();
+    let Some(leaving) = before
+        .map(|tangent| tangent.xz().normalize_or_zero())
+        .filter(|leaving| *leaving != Vec2::ZERO)
+    else {
+        return (chord, chord);
+    };
+    // The arc turns twice as far as the angle between where it leaves and where it ends.
+    let half_sweep = leaving.angle_to(flat).abs();
+    if half_sweep * 2. > MAX_ARC_SWEEP || half_sweep.sin() <= f32::EPSILON {
+        return (chord, chord);
+    }
+    let radius = flat.length() / (2. * half_sweep.sin());
+    // The usual handle length for drawing a circular arc with a single cubic.
+    let handle = 4. / 3. * (half_sweep / 2.).tan() * radius;
+    // The ends of an arc are mirror images of each other across its chord.
+    let along = flat.normalize_or_zero();
+    let arriving = along * 2. * leaving.dot(along) - leaving;
+    let tangent =
+        |direction: Vec2| vec3(direction.x, 0., direction.y) * handle * 3. + Vec3::Y * chord.y;
+    (tangent(leaving), tangent(arriving))
+}
+
+/// Reduces a freehand stroke to the fewest points that stay within tolerance of it.
+pub fn simplify_stroke(stroke: &[Vec3]) -> Vec<Vec3> {
+    if stroke.len() < 3 {
+        return stroke.to_vec();
+    }
+    let last = stroke.len() - 1;
+    let mut keep = vec![false; stroke.len()];
+    keep[0] = true;
+    keep[last] = true;
+    // Ramer-Douglas-Peucker: keep the point farthest from each span until all are close.
+    let mut spans = vec![(0, last)];
+    while let Some((first, last)) = spans.pop() {
+        let (start, end) = (stroke[first].xz(), stroke[last].xz());
+        let farthest = (first + 1..last)
+            .map(|index| (index, distance_to_line(stroke[index].xz(), start, end)))
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((index, distance)) = farthest
+            && distance > STROKE_TOLERANCE
+        {
+            keep[index] = true;
+            spans.push((first, index));
+            spans.push((index, last));
+        }
+    }
+    stroke
+        .iter()
+        .zip(keep)
+        .filter_map(|(point, keep)| keep.then_some(*point))
+        .collect()
+}
+
+fn distance_to_line(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let along = end - start;
+    let t = if along.length_squared() > 0. {
+        ((point - start).dot(along) / along.length_squared()).clamp(0., 1.)
+    } else {
+        0.
+    };
+    point.distance(start + along * t)
+}
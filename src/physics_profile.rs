@@ -0,0 +1,195 @@
+use std::num::NonZeroUsize;
+
+use bevy::{
+    asset::{AssetLoader, LoadContext, io::Reader},
+    prelude::*,
+};
+use bevy_rapier3d::prelude::{
+    Ccd, ColliderMassProperties, Damping, Friction, RapierContextSimulation, Restitution,
+};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::{
+    domino::{Domino, DominoKind},
+    level::LevelLayout,
+};
+
+/// Tuning for domino physics, loaded at startup and again whenever the file changes.
+pub const PHYSICS_PROFILE_PATH: &str = "physics.profile.ron";
+
+/// How every domino behaves physically. Levels can override parts of it.
+#[derive(Resource, Asset, TypePath, Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DominoPhysicsProfile {
+    /// Multiplies each kind's own density.
+    pub density: f32,
+    /// Multiplies each kind's own friction.
+    pub friction: f32,
+    pub restitution: f32,
+    pub linear_damping: f32,
+    pub angular_damping: f32,
+    /// Continuous collision detection, so fast dominoes can't pass through each other.
+    pub ccd: bool,
+    /// Rapier solver iterations per physics tick. More keep contacts steadier.
+    pub solver_iterations: usize,
+}
+
+impl Default for DominoPhysicsProfile {
+    fn default() -> Self {
+        Self {
+            density: 1.,
+            friction: 1.,
+            restitution: 0.,
+            linear_damping: 0.,
+            angular_damping: 0.,
+            ccd: false,
+            solver_iterations: 4,
+        }
+    }
+}
+
+/// Parts of the profile a level sets for itself.
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq)]
+#[serde(default)]
+pub struct DominoPhysicsOverrides {
+    pub density: Option<f32>,
+    pub friction: Option<f32>,
+    pub restitution: Option<f32>,
+    pub linear_damping: Option<f32>,
+    pub angular_damping: Option<f32>,
+    pub ccd: Option<bool>,
+    pub solver_iterations: Option<usize>,
+}
+
+impl DominoPhysicsProfile {
+    /// This profile with a level's overrides on top.
+    pub fn with_overrides(&self, overrides: &DominoPhysicsOverrides) -> Self {
+        Self {
+            density: overrides.density.unwrap_or(self.density),
+            friction: overrides.friction.unwrap_or(self.friction),
+            restitution: overrides.restitution.unwrap_or(self.restitution),
+            linear_damping: overrides.linear_damping.unwrap_or(self.linear_damping),
+            angular_damping: overrides.angular_damping.unwrap_or(self.angular_damping),
+            ccd: overrides.ccd.unwrap_or(self.ccd),
+            solver_iterations: overrides
+                .solver_iterations
+                .unwrap_or(self.solver_iterations),
+        }
+    }
+
+    /// The physical components of a domino of this kind.
+    pub fn domino_physics(&self, kind: DominoKind) -> impl Bundle {
+        (
+            ColliderMassProperties::Density(kind.density() * self.density),
+            Friction::coefficient(kind.friction() * self.friction),
+            Restitution::coefficient(self.restitution),
+            Damping {
+                linear_damping: self.linear_damping,
+                angular_damping: self.angular_damping,
+            },
+            Ccd { enabled: self.ccd },
+        )
+    }
+}
+
+#[derive(Resource)]
+struct PhysicsProfileHandle(Handle<DominoPhysicsProfile>);
+
+pub struct PhysicsProfilePlugin;
+
+impl Plugin for PhysicsProfilePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<DominoPhysicsProfile>()
+            .init_asset_loader::<DominoPhysicsProfileLoader>()
+            .init_resource::<DominoPhysicsProfile>()
+            .add_systems(Startup, load_profile)
+            .add_systems(
+                Update,
+                (
+                    update_profile,
+                    apply_profile.run_if(
+                        resource_changed::<DominoPhysicsProfile>
+                            .or(resource_changed::<LevelLayout>),
+                    ),
+                )
+                    .chain(),
+            );
+    }
+}
+
+fn load_profile(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(PhysicsProfileHandle(
+        asset_server.load(PHYSICS_PROFILE_PATH),
+    ));
+}
+
+/// Takes up the profile once it has loaded, and again each time its file changes.
+fn update_profile(
+    mut events: EventReader<AssetEvent<DominoPhysicsProfile>>,
+    handle: Res<PhysicsProfileHandle>,
+    profiles: Res<Assets<DominoPhysicsProfile>>,
+    mut profile: ResMut<DominoPhysicsProfile>,
+) {
+    for event in events.read() {
+        if let AssetEvent::LoadedWithDependencies { id } | AssetEvent::Modified { id } = event
+            && *id == handle.0.id()
+            && let Some(loaded) = profiles.get(*id)
+        {
+            info!("Loaded {PHYSICS_PROFILE_PATH}");
+            profile.set_if_neq(*loaded);
+        }
+    }
+}
+
+/// Puts the level's profile into effect, on dominoes already standing as well as new ones.
+fn apply_profile(
+    mut commands: Commands,
+    profile: Res<DominoPhysicsProfile>,
+    layout: Res<LevelLayout>,
+    mut contexts: Query<&mut RapierContextSimulation>,
+    domino_query: Query<(Entity, &Domino)>,
+) {
+    let profile = profile.with_overrides(&layout.physics);
+    for mut context in contexts.iter_mut() {
+        context.integration_parameters.num_solver_iterations =
+            NonZeroUsize::new(profile.solver_iterations).unwrap_or(NonZeroUsize::MIN);
+    }
+    for (entity, domino) in domino_query.iter() {
+        commands
+            .entity(entity)
+            .insert(profile.domino_physics(domino.kind));
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum PhysicsProfileLoaderError {
+    #[error("Could not read physics profile: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Could not parse physics profile: {0}")]
+    Ron(#[from] ron::error::SpannedError),
+}
+
+#[derive(Default)]
+pub struct DominoPhysicsProfileLoader;
+
+impl AssetLoader for DominoPhysicsProfileLoader {
+    type Asset = DominoPhysicsProfile;
+    type Settings = ();
+    type Error = PhysicsProfileLoaderError;
+
+    async fn load(
+        &self,
+        reader: &mut dyn Reader,
+        _settings: &(),
+        _load_context: &mut LoadContext<'_>,
+    ) -> Result<Self::Asset, Self::Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).await?;
+        Ok(ron::de::from_bytes::<DominoPhysicsProfile>(&bytes)?)
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["profile.ron"]
+    }
+}
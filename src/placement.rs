@@ -0,0 +1,164 @@
+use bevy::prelude::*;
+
+use crate::domino::{DOMINO_DISTANCE, DominoKind};
+
+/// Samples per curve segment when measuring distance along a curve.
+const SAMPLES_PER_SEGMENT: usize = 200;
+/// With adaptive spacing, turns tighter than this radius bring dominoes closer together.
+const TIGHTEN_RADIUS: f32 = 3.;
+/// The closest adaptive spacing gets, as a fraction of a domino's usual spacing.
+const MIN_SPACING_FACTOR: f32 = 0.8;
+
+/// How dominoes are spaced along a path.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MarkerSpacing {
+    /// Always each kind's own spacing.
+    #[default]
+    Fixed,
+    /// Closer together on sharp turns, so the chain keeps going round them.
+    Adaptive,
+}
+
+impl MarkerSpacing {
+    pub fn toggled(self) -> Self {
+        match self {
+            MarkerSpacing::Fixed => MarkerSpacing::Adaptive,
+            MarkerSpacing::Adaptive => MarkerSpacing::Fixed,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            MarkerSpacing::Fixed => "fixed",
+            MarkerSpacing::Adaptive => "tighter on turns",
+        }
+    }
+}
+
+/// A curve measured by distance along it. The curve's own parameter speeds up and slows
+/// down between control points, so it can't be used to space dominoes evenly.
+pub struct ArcLengthCurve<'a> {
+    curve: &'a CubicCurve<Vec3>,
+    /// Distance from the start at every sample, evenly spaced in the curve's parameter.
+    lengths: Vec<f32>,
+}
+
+impl<'a> ArcLengthCurve<'a> {
+    pub fn new(curve: &'a CubicCurve<Vec3>) -> Self {
+        let samples = SAMPLES_PER_SEGMENT * curve.segments().len();
+        let mut lengths = Vec::with_capacity(samples + 1);
+        let mut length = 0.;
+        let mut last = curve.position(0.);
+        lengths.push(length);
+        for sample in 1..=samples {
+            let position = curve.position(sample as f32 / SAMPLES_PER_SEGMENT as f32);
+            length += position.distance(last);
+            lengths.push(length);
+            last = position;
+        }
+        Self { curve, lengths }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.lengths.last().copied().unwrap_or_default()
+    }
+
+    /// The curve's parameter at a distance along it, clamped to its ends.
+    pub fn t_at(&self, distance: f32) -> f32 {
+        let next = self.lengths.partition_point(|length| *length < distance);
+        if next == 0 {
+            return 0.;
+        }
+        if next == self.lengths.len() {
+            return (self.lengths.len() - 1) as f32 / SAMPLES_PER_SEGMENT as f32;
+        }
+        let (before, after) = (self.lengths[next - 1], self.lengths[next]);
+        let fraction = if after > before {
+            (distance - before) / (after - before)
+        } else {
+            0.
+        };
+        (next - 1) as f32 / SAMPLES_PER_SEGMENT as f32 + fraction / SAMPLES_PER_SEGMENT as f32
+    }
+
+    pub fn position_at(&self, distance: f32) -> Vec3 {
+        self.curve.position(self.t_at(distance))
+    }
+
+    /// Direction of the curve at a distance along it, pointing away from the start.
+    pub fn tangent_at(&self, distance: f32) -> Vec3 {
+        self.curve.velocity(self.t_at(distance)).normalize_or_zero()
+    }
+
+    /// How sharply the curve turns on the floor plane, as one over the radius of the turn.
+    pub fn curvature_at(&self, distance: f32) -> f32 {
+        let t = self.t_at(distance);
+        let velocity = self.curve.velocity(t).xz();
+        let acceleration = self.curve.acceleration(t).xz();
+        let speed = velocity.length();
+        if speed <= f32::EPSILON {
+            return 0.;
+        }
+        velocity.perp_dot(acceleration).abs() / speed.powi(3)
+    }
+
+    /// Index of the segment, and so of the pair of control points, at a distance along it.
+    pub fn segment_at(&self, distance: f32) -> usize {
+        let last = self.curve.segments().len().saturating_sub(1);
+        (self.t_at(distance) as usize).min(last)
+    }
+}
+
+/// Where a domino goes along a path.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    /// Distance along the path from its start.
+    pub distance: f32,
+    pub position: Vec3,
+    /// Direction of the path at the domino, pointing away from the start.
+    pub tangent: Vec3,
+    pub kind: DominoKind,
+}
+
+/// Places dominoes along a curve by distance, starting one spacing from its start.
+/// Each domino is spaced from the one before by that one's kind, given per segment.
+pub fn place_dominoes(
+    curve: &CubicCurve<Vec3>,
+    kinds: &[DominoKind],
+    spacing: MarkerSpacing,
+) -> Vec<Placement> {
+    let arc = ArcLengthCurve::new(curve);
+    let mut placements: Vec<Placement> = Vec::new();
+    let mut distance = DOMINO_DISTANCE;
+    while distance <= arc.length() {
+        let kind = kinds
+            .get(arc.segment_at(distance))
+            .copied()
+            .unwrap_or_default();
+        placements.push(Placement {
+            distance,
+            position: arc.position_at(distance),
+            tangent: arc.tangent_at(distance),
+            kind,
+        });
+
+        let mut step = kind.spacing();
+        if spacing == MarkerSpacing::Adaptive {
+            // Look ahead too, so the domino just before a turn is brought in as well.
+            let curvature = arc
+                .curvature_at(distance)
+                .max(arc.curvature_at(distance + step * 0.5));
+            step *= spacing_factor(curvature);
+        }
+        distance += step;
+    }
+    placements
+}
+
+/// How much to shrink spacing on a turn of the given curvature.
+fn spacing_factor(curvature: f32) -> f32 {
+    if curvature <= 1. / TIGHTEN_RADIUS {
+        return 1.;
+    }
+    (1. / (curvature * TIGHTEN_RADIUS)).max(MIN_SPACING_FACTOR)
+}
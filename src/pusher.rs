@@ -1,29 +1,157 @@
-use crate::environment;
+use crate::{
+    bindings::{Action, ActionState},
+    domino::{self, Domino, DominoMarker},
+    game_state::GameState,
+    level::LevelLayout,
+};
 use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
 
 pub const RADIUS: f32 = 0.25;
 pub const PUSHER_COLOR: Color = Color::srgb(0., 0.75, 0.75);
 
-#[derive(Component)]
-pub struct Pusher;
+/// A kinematic body that drives into the first domino once the run starts.
+#[derive(Component, Default)]
+pub struct Pusher {
+    /// Heading on the floor plane, fixed when the run starts.
+    pub direction: Vec3,
+    pub travelled: f32,
+    pub has_hit: bool,
+}
+
+/// How hard the player chooses to push. Gentler pushes score more.
+#[derive(
+    Resource, Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord,
+)]
+pub enum PushStrength {
+    Gentle,
+    #[default]
+    Normal,
+    Strong,
+}
+
+impl PushStrength {
+    pub fn next(self) -> Self {
+        match self {
+            PushStrength::Gentle => PushStrength::Normal,
+            PushStrength::Normal => PushStrength::Strong,
+            PushStrength::Strong => PushStrength::Gentle,
+        }
+    }
+
+    pub fn describe(self) -> &'static str {
+        match self {
+            PushStrength::Gentle => "gentle",
+            PushStrength::Normal => "normal",
+            PushStrength::Strong => "strong",
+        }
+    }
+
+    /// Scales the level's push speed and impulse.
+    pub fn factor(self) -> f32 {
+        match self {
+            PushStrength::Gentle => 0.6,
+            PushStrength::Normal => 1.,
+            PushStrength::Strong => 1.6,
+        }
+    }
+}
 
 pub struct PusherPlugin;
 
 impl Plugin for PusherPlugin {
     fn build(&self, app: &mut App) {
-        app.add_systems(Update, handle_reset_pusher);
+        app.insert_resource(PushStrength::default())
+            .add_systems(OnEnter(GameState::Drawing), reset_pusher)
+            .add_systems(
+                OnEnter(GameState::Simulating),
+                aim_pusher.before(domino::spawn_dominoes),
+            )
+            .add_systems(
+                FixedUpdate,
+                drive_pusher.run_if(in_state(GameState::Simulating)),
+            )
+            .add_systems(
+                Update,
+                (
+                    reset_pusher.run_if(resource_changed::<LevelLayout>),
+                    cycle_strength.run_if(in_state(GameState::Drawing)),
+                    shove_first_domino.run_if(in_state(GameState::Simulating)),
+                ),
+            );
+    }
+}
+
+/// Puts the pusher back at the start, ready for the next push.
+fn reset_pusher(mut pusher: Single<(&mut Pusher, &mut Transform)>, layout: Res<LevelLayout>) {
+    let (pusher, transform) = &mut *pusher;
+    **pusher = Pusher::default();
+    transform.translation = layout.pusher_position();
+}
+
+fn cycle_strength(actions: Res<ActionState>, mut strength: ResMut<PushStrength>) {
+    if actions.just_pressed(Action::CycleStrength) {
+        *strength = strength.next();
+    }
+}
+
+/// Points the pusher along the level's heading, or at the first domino of the main chain.
+fn aim_pusher(
+    mut pusher: Single<(&mut Pusher, &Transform)>,
+    layout: Res<LevelLayout>,
+    markers: Query<(&DominoMarker, &Transform)>,
+) {
+    let (pusher, transform) = &mut *pusher;
+    let direction = match layout.push.direction {
+        Some(degrees) => Quat::from_rotation_y(degrees.to_radians()) * Vec3::X,
+        None => markers
+            .iter()
+            .find(|(marker, _)| marker.chain == 0 && marker.index == 0)
+            .map_or(Vec3::ZERO, |(_, marker)| {
+                (marker.translation - transform.translation).with_y(0.)
+            }),
+    };
+    pusher.direction = direction.normalize_or_zero();
+}
+
+// #[hot]
+pub fn drive_pusher(
+    mut pusher: Single<(&mut Pusher, &mut Transform)>,
+    layout: Res<LevelLayout>,
+    strength: Res<PushStrength>,
+    time: Res<Time>,
+) {
+    let (pusher, transform) = &mut *pusher;
+    let remaining = layout.push.distance - pusher.travelled;
+    if remaining <= 0. {
+        return;
     }
+    let step = (layout.push.speed * strength.factor() * time.delta_secs()).min(remaining);
+    transform.translation += pusher.direction * step;
+    pusher.travelled += step;
 }
-fn handle_reset_pusher(
-    mut transform: Single<&mut Transform, With<Pusher>>,
-    keyboard: Res<ButtonInput<KeyCode>>,
+
+/// Gives the first domino the pusher touches the level's extra impulse.
+fn shove_first_domino(
+    mut commands: Commands,
+    mut pusher: Single<(&mut Pusher, &CollidingEntities)>,
+    layout: Res<LevelLayout>,
+    strength: Res<PushStrength>,
+    domino_query: Query<(), With<Domino>>,
 ) {
-    if keyboard.just_pressed(KeyCode::Space)
-        || keyboard.just_pressed(KeyCode::KeyR)
-        || keyboard.just_pressed(KeyCode::KeyZ)
-        || keyboard.just_pressed(KeyCode::KeyC)
-        || keyboard.just_pressed(KeyCode::KeyN)
-    {
-        transform.translation = environment::PUSHER_START_POS;
+    let (pusher, colliding) = &mut *pusher;
+    if pusher.has_hit {
+        return;
+    }
+    let Some(domino) = colliding.iter().find(|e| domino_query.contains(*e)) else {
+        return;
+    };
+    pusher.has_hit = true;
+    if layout.push.impulse > 0. {
+        commands.entity(domino).insert(ExternalImpulse {
+            impulse: pusher.direction * layout.push.impulse * strength.factor(),
+            ..default()
+        });
     }
 }
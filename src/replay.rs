@@ -0,0 +1,479 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bindings::{Action, ActionState},
+    curve::{Chain, ControlPoints},
+    curve_history::{CurveEdit, CurveHistory},
+    domino::{Domino, DominoKind, DominoMarker},
+    game_state::{self, GameState},
+    level::LevelLayout,
+    mechanism::Mechanism,
+    pusher::Pusher,
+};
+
+/// Saved replays go here, one per level, so they can be shared.
+pub const REPLAY_DIR: &str = "replays";
+/// Seconds between recorded frames. Playback interpolates between them.
+const RECORD_INTERVAL: f32 = 1. / 30.;
+/// Recording stops after this long, so a run left to settle can't grow without bound.
+const MAX_RECORD_SECONDS: f32 = 90.;
+/// Playback speeds stepped through with the speed controls.
+pub const PLAYBACK_SPEEDS: [f32; 5] = [0.1, 0.25, 0.5, 1., 2.];
+/// How fast holding a scrub control moves through the replay, in seconds per second.
+const SCRUB_SPEED: f32 = 3.;
+
+/// Dominoes from a physics run, or the markers for the next one.
+type PathDomino = Or<(With<Domino>, With<DominoMarker>)>;
+/// Keeps mechanisms apart from the other bodies posed during playback.
+type ReplayedMechanism = (Without<ReplayedDomino>, Without<Pusher>);
+
+/// Where a body was at one moment.
+#[derive(Serialize, Deserialize, Default, Clone, Copy, Debug, PartialEq)]
+pub struct Pose {
+    pub translation: Vec3,
+    pub rotation: Quat,
+}
+
+impl Pose {
+    fn of(transform: &Transform) -> Self {
+        Self {
+            translation: transform.translation,
+            rotation: transform.rotation,
+        }
+    }
+
+    fn lerp(self, other: Pose, t: f32) -> Self {
+        Self {
+            translation: self.translation.lerp(other.translation, t),
+            rotation: self.rotation.slerp(other.rotation, t),
+        }
+    }
+}
+
+/// A domino as it was set up for the run.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct ReplayDomino {
+    pub chain: usize,
+    pub index: usize,
+    pub kind: DominoKind,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct ReplayFrame {
+    /// Seconds since the push.
+    pub seconds: f32,
+    pub pusher: Pose,
+    /// In the order of [`Replay::dominoes`].
+    pub dominoes: Vec<Pose>,
+    /// In the order of the level's mechanisms.
+    pub mechanisms: Vec<Pose>,
+}
+
+/// Everything moving during a physics run, frame by frame, with the path that set it up.
+#[derive(Resource, Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct Replay {
+    /// Save key of the level it was recorded on, see [`crate::level_asset::LevelDefinition::save_key`].
+    pub level: String,
+    pub chains: Vec<Chain>,
+    pub dominoes: Vec<ReplayDomino>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn duration(&self) -> f32 {
+        self.frames.last().map_or(0., |frame| frame.seconds)
+    }
+
+    /// The frame at a moment, blended from the recorded frames either side of it.
+    pub fn frame_at(&self, seconds: f32) -> Option<ReplayFrame> {
+        let next = self
+            .frames
+            .partition_point(|frame| frame.seconds < seconds)
+            .min(self.frames.len().checked_sub(1)?);
+        let Some(before) = next.checked_sub(1).map(|index| &self.frames[index]) else {
+            return self.frames.first().cloned();
+        };
+        let after = &self.frames[next];
+        let span = after.seconds - before.seconds;
+        let t = if span > 0. {
+            ((seconds - before.seconds) / span).clamp(0., 1.)
+        } else {
+            1.
+        };
+        let blend = |a: &[Pose], b: &[Pose]| -> Vec<Pose> {
+            a.iter().zip(b).map(|(a, b)| a.lerp(*b, t)).collect()
+        };
+        Some(ReplayFrame {
+            seconds,
+            pusher: before.pusher.lerp(after.pusher, t),
+            dominoes: blend(&before.dominoes, &after.dominoes),
+            mechanisms: blend(&before.mechanisms, &after.mechanisms),
+        })
+    }
+}
+
+/// The run being recorded, or the last one recorded, kept until the next push.
+#[derive(Resource, Default, Debug)]
+struct Recording {
+    replay: Replay,
+    elapsed: f32,
+    /// When the next frame is due, in seconds since the push.
+    next_frame: f32,
+    /// The domino entities in the order of [`Replay::dominoes`].
+    entities: Vec<Entity>,
+}
+
+/// Where playback is up to and how it is moving.
+#[derive(Resource, Debug)]
+pub struct Playback {
+    pub seconds: f32,
+    /// Index into [`PLAYBACK_SPEEDS`].
+    pub speed: usize,
+    pub is_paused: bool,
+    pub is_reversed: bool,
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            seconds: 0.,
+            speed: PLAYBACK_SPEEDS
+                .iter()
+                .position(|speed| *speed == 1.)
+                .unwrap_or(0),
+            is_paused: false,
+            is_reversed: false,
+        }
+    }
+}
+
+impl Playback {
+    pub fn speed(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed]
+    }
+}
+
+/// Stands in for a domino during playback, with no physics of its own.
+#[derive(Component)]
+struct ReplayedDomino {
+    /// Position in [`Replay::dominoes`].
+    index: usize,
+}
+
+pub struct ReplayPlugin;
+
+impl Plugin for ReplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Recording::default())
+            .insert_resource(Replay::default())
+            .insert_resource(Playback::default())
+            .add_systems(OnEnter(GameState::Simulating), start_recording)
+            .add_systems(OnEnter(GameState::Replaying), start_playback)
+            .add_systems(
+                Update,
+                (
+                    record_frame.run_if(game_state::after_push),
+                    (open_replay, save_replay).run_if(can_open_replay),
+                    (control_playback, pose_playback, load_replay_path)
+                        .chain()
+                        .run_if(in_state(GameState::Replaying)),
+                ),
+            );
+    }
+}
+
+/// Replays can be opened while drawing or once a run is over.
+fn can_open_replay(state: Res<State<GameState>>) -> bool {
+    matches!(
+        state.get(),
+        GameState::Drawing | GameState::Won | GameState::Failed | GameState::AllComplete
+    )
+}
+
+fn start_recording(
+    layout: Res<LevelLayout>,
+    control_points: Res<ControlPoints>,
+    mut recording: ResMut<Recording>,
+) {
+    *recording = Recording {
+        replay: Replay {
+            level: layout.save_key().to_owned(),
+            chains: control_points.chains.clone(),
+            ..default()
+        },
+        ..default()
+    };
+}
+
+fn record_frame(
+    time: Res<Time>,
+    domino_query: Query<(Entity, &Domino, &Transform)>,
+    pusher: Single<&Transform, With<Pusher>>,
+    mechanism_query: Query<(&Mechanism, &Transform)>,
+    mut recording: ResMut<Recording>,
+) {
+    // The dominoes are spawned as the run starts, so are only there from the first frame on.
+    if recording.entities.is_empty() {
+        let mut dominoes: Vec<_> = domino_query
+            .iter()
+            .map(|(entity, domino, _)| (entity, domino))
+            .collect();
+        dominoes.sort_by_key(|(_, domino)| (domino.chain, domino.index));
+        recording.entities = dominoes.iter().map(|(entity, _)| *entity).collect();
+        recording.replay.dominoes = dominoes
+            .iter()
+            .map(|(_, domino)| ReplayDomino {
+                chain: domino.chain,
+                index: domino.index,
+                kind: domino.kind,
+            })
+            .collect();
+    }
+
+    let seconds = recording.elapsed;
+    recording.elapsed += time.delta_secs();
+    if seconds < recording.next_frame || seconds > MAX_RECORD_SECONDS {
+        return;
+    }
+    recording.next_frame += RECORD_INTERVAL;
+    let dominoes = recording
+        .entities
+        .iter()
+        .filter_map(|entity| domino_query.get(*entity).ok())
+        .map(|(_, _, transform)| Pose::of(transform))
+        .collect();
+    let mut mechanisms: Vec<_> = mechanism_query.iter().collect();
+    mechanisms.sort_by_key(|(mechanism, _)| mechanism.index);
+    let frame = ReplayFrame {
+        seconds,
+        pusher: Pose::of(&pusher),
+        dominoes,
+        mechanisms: mechanisms
+            .iter()
+            .map(|(_, transform)| Pose::of(transform))
+            .collect(),
+    };
+    recording.replay.frames.push(frame);
+}
+
+/// Plays the last run on this level, or the one saved for it if there hasn't been one yet.
+fn open_replay(
+    actions: Res<ActionState>,
+    layout: Res<LevelLayout>,
+    recording: Res<Recording>,
+    mut replay: ResMut<Replay>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(Action::Replay) {
+        return;
+    }
+    let key = layout.save_key();
+    let found = if recording.replay.level == key && !recording.replay.frames.is_empty() {
+        Some(recording.replay.clone())
+    } else {
+        read_replay_file(key).filter(|replay| replay.level == key)
+    };
+    match found {
+        Some(found) => {
+            *replay = found;
+            next_state.set(GameState::Replaying);
+        }
+        None => info!("No replay of {} to play", layout.name),
+    }
+}
+
+fn save_replay(actions: Res<ActionState>, recording: Res<Recording>) {
+    if actions.just_pressed(Action::SaveReplay) && !recording.replay.frames.is_empty() {
+        write_replay_file(&recording.replay);
+    }
+}
+
+/// Takes the physics dominoes and markers away and puts replayed dominoes in their place.
+fn start_playback(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    replay: Res<Replay>,
+    query: Query<Entity, PathDomino>,
+    mut playback: ResMut<Playback>,
+) {
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+    for (index, domino) in replay.dominoes.iter().enumerate() {
+        commands.spawn((
+            Name::new("Replayed Domino"),
+            StateScoped(GameState::Replaying),
+            ReplayedDomino { index },
+            Mesh3d(meshes.add(Cuboid::from_size(domino.kind.size()))),
+            MeshMaterial3d(materials.add(domino.kind.color())),
+            Transform::default(),
+        ));
+    }
+    *playback = Playback::default();
+}
+
+/// Swaps the player's path for the replay's and goes back to drawing, as an edit that can
+/// be undone.
+fn load_replay_path(
+    actions: Res<ActionState>,
+    replay: Res<Replay>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if !actions.just_pressed(Action::LoadReplayPath) {
+        return;
+    }
+    if replay.chains != control_points.chains {
+        let edit = CurveEdit::Replace {
+            before: control_points.chains.clone(),
+            after: replay.chains.clone(),
+        };
+        history.apply(edit, &mut control_points);
+    }
+    next_state.set(GameState::Drawing);
+}
+
+fn control_playback(
+    time: Res<Time>,
+    actions: Res<ActionState>,
+    replay: Res<Replay>,
+    mut playback: ResMut<Playback>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if actions.just_pressed(Action::Stop) || actions.just_pressed(Action::Replay) {
+        next_state.set(GameState::Drawing);
+        return;
+    }
+    let duration = replay.duration();
+    let is_at_end = if playback.is_reversed {
+        playback.seconds <= 0.
+    } else {
+        playback.seconds >= duration
+    };
+    if actions.just_pressed(Action::Pause) {
+        // Playing again from the end starts over.
+        if playback.is_paused && is_at_end {
+            playback.seconds = if playback.is_reversed { duration } else { 0. };
+        }
+        playback.is_paused = !playback.is_paused;
+    }
+    if actions.just_pressed(Action::Reverse) {
+        playback.is_reversed = !playback.is_reversed;
+    }
+    if actions.just_pressed(Action::SlowDown) {
+        playback.speed = playback.speed.saturating_sub(1);
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        playback.speed = (playback.speed + 1).min(PLAYBACK_SPEEDS.len() - 1);
+    }
+
+    let delta = time.delta_secs();
+    if actions.pressed(Action::ScrubBack) {
+        playback.seconds -= delta * SCRUB_SPEED;
+    } else if actions.pressed(Action::ScrubForward) {
+        playback.seconds += delta * SCRUB_SPEED;
+    } else if !playback.is_paused {
+        let direction = if playback.is_reversed { -1. } else { 1. };
+        playback.seconds += delta * playback.speed() * direction;
+    }
+    // Playback stops at either end rather than looping.
+    if !(0. ..=duration).contains(&playback.seconds) {
+        playback.is_paused = true;
+        playback.seconds = playback.seconds.clamp(0., duration);
+    }
+}
+
+fn pose_playback(
+    replay: Res<Replay>,
+    playback: Res<Playback>,
+    mut domino_query: Query<(&ReplayedDomino, &mut Transform)>,
+    mut pusher: Single<&mut Transform, (With<Pusher>, Without<ReplayedDomino>)>,
+    mut mechanism_query: Query<(&Mechanism, &mut Transform), ReplayedMechanism>,
+) {
+    let Some(frame) = replay.frame_at(playback.seconds) else {
+        return;
+    };
+    let apply = |transform: &mut Transform, pose: &Pose| {
+        transform.translation = pose.translation;
+        transform.rotation = pose.rotation;
+    };
+    for (domino, mut transform) in domino_query.iter_mut() {
+        if let Some(pose) = frame.dominoes.get(domino.index) {
+            apply(&mut transform, pose);
+        }
+    }
+    apply(&mut pusher, &frame.pusher);
+    for (mechanism, mut transform) in mechanism_query.iter_mut() {
+        if let Some(pose) = frame.mechanisms.get(mechanism.index) {
+            apply(&mut transform, pose);
+        }
+    }
+}
+
+/// A file name for a level's replay, from its save key.
+fn replay_file_name(level: &str) -> String {
+    let name: String = level
+        .trim_end_matches(".level.ron")
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if name.is_empty() {
+        "free_mode.replay.ron".into()
+    } else {
+        format!("{name}.replay.ron")
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_replay_file(level: &str) -> Option<Replay> {
+    let path = std::path::Path::new(REPLAY_DIR).join(replay_file_name(level));
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return None,
+        Err(err) => {
+            error!("Could not read {}: {err}", path.display());
+            return None;
+        }
+    };
+    ron::from_str(&text)
+        .inspect_err(|err| error!("Could not parse {}: {err}", path.display()))
+        .ok()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_replay_file(replay: &Replay) {
+    let path = std::path::Path::new(REPLAY_DIR).join(replay_file_name(&replay.level));
+    // Replays are long, so they are written without pretty printing.
+    let text = match ron::to_string(replay) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("Could not serialize replay: {err}");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::create_dir_all(REPLAY_DIR) {
+        error!("Could not create {REPLAY_DIR}: {err}");
+        return;
+    }
+    match std::fs::write(&path, text) {
+        Ok(()) => info!("Saved replay to {}", path.display()),
+        Err(err) => error!("Could not write {}: {err}", path.display()),
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_replay_file(_level: &str) -> Option<Replay> {
+    None
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_replay_file(_replay: &Replay) {}
@@ -0,0 +1,227 @@
+use std::{collections::BTreeMap, time::Duration};
+
+use bevy::{prelude::*, time::common_conditions::on_timer};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    curve::{self, Chain, ControlPoints},
+    curve_history::{CurveEdit, CurveHistory},
+    domino::Domino,
+    game_state::GameState,
+    goal::GoalProgress,
+    level::{Level, LevelLayout, Levels},
+    score::{self, RunScore},
+};
+
+pub const SAVE_PATH: &str = "save.ron";
+/// How often unsaved progress is written to disk.
+const SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Everything kept between runs of the game.
+#[derive(Resource, Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct SaveData {
+    /// The level being played when the game was last closed.
+    #[serde(default)]
+    pub current_level: usize,
+    /// Progress on each level, by [`crate::level_asset::LevelDefinition::save_key`].
+    #[serde(default)]
+    pub levels: BTreeMap<String, LevelSave>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, PartialEq)]
+pub struct LevelSave {
+    /// The path as the player last left it.
+    #[serde(default)]
+    pub chains: Vec<Chain>,
+    #[serde(default)]
+    pub is_complete: bool,
+    #[serde(default)]
+    pub best_seconds: Option<f32>,
+    #[serde(default)]
+    pub fewest_dominoes: Option<usize>,
+    #[serde(default)]
+    pub best_score: Option<u32>,
+    /// Most stars earned in a single run.
+    #[serde(default)]
+    pub stars: usize,
+}
+
+impl SaveData {
+    pub fn level(&self, name: &str) -> Option<&LevelSave> {
+        self.levels.get(name)
+    }
+}
+
+pub struct SavePlugin;
+
+impl Plugin for SavePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(read_save_file())
+            .add_systems(
+                Update,
+                (
+                    (restore_solution, record_path)
+                        .chain()
+                        .after(curve::setup_curve),
+                    write_save.run_if(on_timer(SAVE_INTERVAL)),
+                ),
+            )
+            .add_systems(OnExit(GameState::Loading), restore_level)
+            .add_systems(OnEnter(GameState::Won), record_win.after(score::score_win))
+            .add_systems(
+                OnEnter(GameState::AllComplete),
+                record_win.after(score::score_win),
+            )
+            .add_systems(Last, write_save_on_exit);
+    }
+}
+
+/// Continues from the level that was being played last time.
+fn restore_level(mut save: ResMut<SaveData>, levels: Res<Levels>, mut level: ResMut<Level>) {
+    migrate_named_levels(&mut save, &levels);
+    level.value = save.current_level.min(levels.definitions.len());
+}
+
+/// Moves progress saved by level name, as older saves did, to the level's save key. Names that
+/// aren't unique can't be told apart, so their progress is left where it is.
+fn migrate_named_levels(save: &mut SaveData, levels: &Levels) {
+    for definition in &levels.definitions {
+        let unique = levels
+            .definitions
+            .iter()
+            .filter(|other| other.name == definition.name)
+            .count()
+            == 1;
+        if !unique || definition.name.is_empty() || save.levels.contains_key(definition.save_key())
+        {
+            continue;
+        }
+        if let Some(saved) = save.levels.remove(&definition.name) {
+            save.levels.insert(definition.save_key().to_owned(), saved);
+        }
+    }
+}
+
+fn restore_solution(
+    layout: Res<LevelLayout>,
+    save: Res<SaveData>,
+    mut control_points: ResMut<ControlPoints>,
+    mut history: ResMut<CurveHistory>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    let Some(saved) = save.level(layout.save_key()) else {
+        return;
+    };
+    if saved.chains.is_empty() || saved.chains == control_points.chains {
+        return;
+    }
+    let mut after = saved.chains.clone();
+    // The path must still start at the pusher if the level has been edited since.
+    if let Some(start) = after[0].points.first_mut() {
+        *start = layout.control_start();
+    }
+    let edit = CurveEdit::Replace {
+        before: control_points.chains.clone(),
+        after,
+    };
+    history.apply(edit, &mut control_points);
+}
+
+fn record_path(
+    layout: Res<LevelLayout>,
+    level: Res<Level>,
+    control_points: Res<ControlPoints>,
+    state: Res<State<GameState>>,
+    mut save: ResMut<SaveData>,
+) {
+    if level.is_changed() && save.current_level != level.value {
+        save.current_level = level.value;
+    }
+    if !control_points.is_changed() || *state.get() == GameState::Editing {
+        return;
+    }
+    let entry = save.levels.entry(layout.save_key().to_owned()).or_default();
+    if entry.chains != control_points.chains {
+        entry.chains.clone_from(&control_points.chains);
+    }
+}
+
+fn record_win(
+    layout: Res<LevelLayout>,
+    progress: Res<GoalProgress>,
+    domino_query: Query<(), With<Domino>>,
+    score: Res<RunScore>,
+    mut save: ResMut<SaveData>,
+) {
+    let dominoes = domino_query.iter().count();
+    let entry = save.levels.entry(layout.save_key().to_owned()).or_default();
+    entry.is_complete = true;
+    entry.best_seconds = Some(
+        entry
+            .best_seconds
+            .map_or(progress.elapsed, |best| best.min(progress.elapsed)),
+    );
+    entry.fewest_dominoes = Some(
+        entry
+            .fewest_dominoes
+            .map_or(dominoes, |best| best.min(dominoes)),
+    );
+    entry.best_score = Some(
+        entry
+            .best_score
+            .map_or(score.points, |best| best.max(score.points)),
+    );
+    entry.stars = entry.stars.max(score.stars);
+}
+
+fn write_save(save: Res<SaveData>) {
+    if save.is_changed() {
+        write_save_file(&save);
+    }
+}
+
+fn write_save_on_exit(mut exit_events: EventReader<AppExit>, save: Res<SaveData>) {
+    if exit_events.read().next().is_some() {
+        write_save_file(&save);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn read_save_file() -> SaveData {
+    let text = match std::fs::read_to_string(SAVE_PATH) {
+        Ok(text) => text,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return SaveData::default(),
+        Err(err) => {
+            error!("Could not read {SAVE_PATH}: {err}");
+            return SaveData::default();
+        }
+    };
+    ron::from_str(&text).unwrap_or_else(|err| {
+        error!("Could not parse {SAVE_PATH}, starting fresh: {err}");
+        SaveData::default()
+    })
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn write_save_file(save: &SaveData) {
+    let text = match ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default()) {
+        Ok(text) => text,
+        Err(err) => {
+            error!("Could not serialize save data: {err}");
+            return;
+        }
+    };
+    if let Err(err) = std::fs::write(SAVE_PATH, text) {
+        error!("Could not write {SAVE_PATH}: {err}");
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn read_save_file() -> SaveData {
+    SaveData::default()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn write_save_file(_save: &SaveData) {}
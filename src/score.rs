@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+
+use crate::{
+    curve::{self, ControlPoints},
+    domino::Domino,
+    game_state::GameState,
+    goal::GoalProgress,
+    level::LevelLayout,
+    level_asset::LevelDefinition,
+    pusher::PushStrength,
+};
+
+/// Points for winning at all.
+pub const COMPLETION_POINTS: u32 = 1000;
+/// Points for each domino left unused under the level's budget.
+const UNUSED_DOMINO_POINTS: u32 = 10;
+/// Points for a path as short as the straight line from the pusher to the nearest goal.
+const PATH_POINTS: f32 = 500.;
+/// Points for each second the goals were reached before [`TIME_PAR`].
+const TIME_POINTS: f32 = 10.;
+const TIME_PAR: f32 = 60.;
+/// Points for pushing gently, lost for pushing hard.
+const PUSH_POINTS: i32 = 100;
+/// Star thresholds for levels that don't set their own. A win always earns the first.
+pub const DEFAULT_STARS: [u32; 3] = [COMPLETION_POINTS, 1500, 1800];
+
+/// The score of the most recent winning run.
+#[derive(Resource, Default, Debug, Clone, Copy, PartialEq)]
+pub struct RunScore {
+    pub points: u32,
+    pub stars: usize,
+}
+
+pub struct ScorePlugin;
+
+impl Plugin for ScorePlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(RunScore::default())
+            .add_systems(OnEnter(GameState::Won), score_win)
+            .add_systems(OnEnter(GameState::AllComplete), score_win);
+    }
+}
+
+/// How many stars a level can award.
+pub fn max_stars(level: &LevelDefinition) -> usize {
+    star_thresholds(level).len()
+}
+
+fn star_thresholds(level: &LevelDefinition) -> &[u32] {
+    if level.stars.is_empty() {
+        &DEFAULT_STARS
+    } else {
+        &level.stars
+    }
+}
+
+/// Scores a winning run on dominoes used, path length, time to reach the goals
+/// and how hard the pusher pushed.
+pub fn score_run(
+    level: &LevelDefinition,
+    dominoes: usize,
+    path_length: f32,
+    seconds: f32,
+    strength: PushStrength,
+) -> RunScore {
+    let unused = level
+        .max_dominoes
+        .map_or(0, |max| (max as usize).saturating_sub(dominoes));
+    let straight = level
+        .goals
+        .iter()
+        .map(|goal| goal.distance(level.pusher))
+        .reduce(f32::min)
+        .unwrap_or(0.);
+    let directness = if path_length > 0. {
+        (straight / path_length).min(1.)
+    } else {
+        0.
+    };
+    let points = COMPLETION_POINTS
+        + unused as u32 * UNUSED_DOMINO_POINTS
+        + (directness * PATH_POINTS).round() as u32
+        + ((TIME_PAR - seconds).max(0.) * TIME_POINTS).round() as u32;
+    let points = points.saturating_add_signed(match strength {
+        PushStrength::Gentle => PUSH_POINTS,
+        PushStrength::Normal => 0,
+        PushStrength::Strong => -PUSH_POINTS,
+    });
+
+    let stars = star_thresholds(level)
+        .iter()
+        .filter(|threshold| points >= **threshold)
+        .count()
+        .max(1);
+    RunScore { points, stars }
+}
+
+pub fn score_win(
+    layout: Res<LevelLayout>,
+    progress: Res<GoalProgress>,
+    control_points: Res<ControlPoints>,
+    domino_query: Query<(), With<Domino>>,
+    strength: Res<PushStrength>,
+    mut score: ResMut<RunScore>,
+) {
+    *score = score_run(
+        &layout,
+        domino_query.iter().count(),
+        curve::path_length(&control_points),
+        progress.elapsed,
+        *strength,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A level with the goal 20 units straight ahead of the pusher.
+    fn level() -> LevelDefinition {
+        LevelDefinition {
+            goals: vec![vec2(-10., 0.)],
+            pusher: vec2(10., 0.),
+            ..default()
+        }
+    }
+
+    /// Scores a run that goes nowhere near directly and reaches the goal on par.
+    fn score_plain(level: &LevelDefinition, dominoes: usize, strength: PushStrength) -> RunScore {
+        score_run(level, dominoes, 0., TIME_PAR, strength)
+    }
+
+    #[test]
+    fn unused_dominoes_under_the_budget_score() {
+        let budgeted = LevelDefinition {
+            max_dominoes: Some(30),
+            ..level()
+        };
+        let points = |dominoes| score_plain(&budgeted, dominoes, PushStrength::Normal).points;
+        assert_eq!(points(20), COMPLETION_POINTS + 10 * UNUSED_DOMINO_POINTS);
+        assert_eq!(points(30), COMPLETION_POINTS);
+        assert_eq!(points(40), COMPLETION_POINTS);
+        assert_eq!(
+            score_plain(&level(), 20, PushStrength::Normal).points,
+            COMPLETION_POINTS
+        );
+    }
+
+    #[test]
+    fn empty_path_scores_no_directness() {
+        let score = score_plain(&level(), 10, PushStrength::Normal);
+        assert_eq!(score.points, COMPLETION_POINTS);
+        assert_eq!(score.stars, 1);
+        let direct = score_run(&level(), 10, 20., TIME_PAR, PushStrength::Normal);
+        assert_eq!(direct.points, COMPLETION_POINTS + PATH_POINTS as u32);
+    }
+
+    #[test]
+    fn overtime_costs_nothing_extra() {
+        let on_par = score_run(&level(), 10, 0., TIME_PAR, PushStrength::Normal);
+        let overtime = score_run(&level(), 10, 0., TIME_PAR * 3., PushStrength::Normal);
+        let early = score_run(&level(), 10, 0., TIME_PAR - 10., PushStrength::Normal);
+        assert_eq!(overtime, on_par);
+        assert_eq!(early.points, on_par.points + 10 * TIME_POINTS as u32);
+    }
+
+    #[test]
+    fn strong_push_at_the_lowest_score_still_earns_a_star() {
+        let strong = score_plain(&level(), 10, PushStrength::Strong);
+        assert_eq!(strong.points, COMPLETION_POINTS - PUSH_POINTS as u32);
+        assert_eq!(strong.stars, 1);
+        let gentle = score_plain(&level(), 10, PushStrength::Gentle);
+        assert_eq!(gentle.points, COMPLETION_POINTS + PUSH_POINTS as u32);
+    }
+
+    #[test]
+    fn levels_without_stars_use_the_defaults() {
+        assert_eq!(max_stars(&level()), DEFAULT_STARS.len());
+        let best = score_run(&level(), 10, 20., 30., PushStrength::Normal);
+        assert_eq!(best.points, DEFAULT_STARS[2]);
+        assert_eq!(best.stars, 3);
+
+        let starred = LevelDefinition {
+            stars: vec![COMPLETION_POINTS, 2000],
+            ..level()
+        };
+        assert_eq!(max_stars(&starred), 2);
+        assert_eq!(
+            score_run(&starred, 10, 20., 30., PushStrength::Normal).stars,
+            1
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use bevy::prelude::*;
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    environment::FLOOR_HALF_SIZE,
+    level::LevelLayout,
+    level_asset::{TerrainDefinition, TerrainKind},
+};
+
+const TERRAIN_COLOR: Color = Color::srgb(0.55, 0.5, 0.45);
+/// Thinnest a ramp's slab gets, so even a shallow ramp has some body.
+const MIN_RAMP_THICKNESS: f32 = 0.1;
+
+/// Part of a platform, staircase or ramp, which dominoes and the cursor can stand on.
+#[derive(Component)]
+pub struct Terrain;
+
+pub struct TerrainPlugin;
+
+impl Plugin for TerrainPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, spawn_terrain);
+    }
+}
+
+/// Replaces the terrain whenever a level is spawned or edited.
+fn spawn_terrain(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    layout: Res<LevelLayout>,
+    query: Query<Entity, With<Terrain>>,
+) {
+    if !layout.is_changed() {
+        return;
+    }
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let material = materials.add(TERRAIN_COLOR);
+    for terrain in &layout.terrain {
+        for (size, transform) in blocks(terrain) {
+            commands.spawn((
+                Name::new("Terrain"),
+                Terrain,
+                RigidBody::Fixed,
+                Collider::cuboid(size.x * 0.5, size.y * 0.5, size.z * 0.5),
+                Friction::new(1.),
+                Restitution::new(0.),
+                Mesh3d(meshes.add(Cuboid::from_size(size))),
+                MeshMaterial3d(material.clone()),
+                transform,
+            ));
+        }
+    }
+}
+
+/// The cuboids a piece of terrain is built from, each with its size and placement.
+/// Their tops match [`TerrainDefinition::surface_at`].
+fn blocks(terrain: &TerrainDefinition) -> Vec<(Vec3, Transform)> {
+    let rotation = terrain.rotation();
+    let base = vec3(terrain.position.x, FLOOR_HALF_SIZE.y, terrain.position.y);
+    let (length, width, height) = (terrain.size.x, terrain.size.y, terrain.height);
+    match terrain.kind {
+        TerrainKind::Platform => {
+            let size = vec3(length, height, width);
+            let transform =
+                Transform::from_translation(base + Vec3::Y * height * 0.5).with_rotation(rotation);
+            vec![(size, transform)]
+        }
+        TerrainKind::Stairs { steps } => {
+            let steps = steps.max(1);
+            let step_length = length / steps as f32;
+            (0..steps)
+                .map(|step| {
+                    let step_height = height * (step + 1) as f32 / steps as f32;
+                    let size = vec3(step_length, step_height, width);
+                    let x = -length * 0.5 + (step as f32 + 0.5) * step_length;
+                    let offset = rotation * vec3(x, step_height * 0.5, 0.);
+                    let transform =
+                        Transform::from_translation(base + offset).with_rotation(rotation);
+                    (size, transform)
+                })
+                .collect()
+        }
+        TerrainKind::Ramp => {
+            // A tilted slab whose top runs from the floor at the low end to the full height,
+            // thick enough that its underside reaches the floor at the high end.
+            let slope = height.atan2(length);
+            let thickness = (height * slope.cos()).max(MIN_RAMP_THICKNESS);
+            let size = vec3(length.hypot(height), thickness, width);
+            let tilt = Quat::from_rotation_z(slope);
+            let top_middle = vec3(0., height * 0.5, 0.);
+            let centre = top_middle - tilt * Vec3::Y * thickness * 0.5;
+            let transform = Transform::from_translation(base + rotation * centre)
+                .with_rotation(rotation * tilt);
+            vec![(size, transform)]
+        }
+    }
+}
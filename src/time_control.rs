@@ -0,0 +1,206 @@
+use bevy::{input::InputSystem, prelude::*};
+use bevy_rapier3d::prelude::*;
+
+use crate::{
+    bindings::{self, Action, ActionState},
+    game_state,
+};
+
+/// Length of one physics tick, in seconds. Rapier, the pusher and mechanisms all step by it.
+pub const PHYSICS_TIMESTEP: f32 = 1. / 60.;
+/// Speeds the physics can run at, stepped through with the speed controls.
+pub const TIME_SPEEDS: [f32; 4] = [0.25, 0.5, 1., 2.];
+
+/// How fast the physics runs once the dominoes have been pushed.
+/// Everything on the fixed schedule follows the game's virtual clock, so scaling or pausing
+/// that clock slows or stops Rapier and the pusher together.
+#[derive(Resource, Debug, Clone, Copy, PartialEq)]
+pub struct TimeControl {
+    /// Index into [`TIME_SPEEDS`].
+    pub speed: usize,
+    pub is_paused: bool,
+    /// A step asked for while time was running, made on the next frame once it has stopped.
+    pub is_step_pending: bool,
+}
+
+impl Default for TimeControl {
+    fn default() -> Self {
+        Self {
+            speed: TIME_SPEEDS
+                .iter()
+                .position(|speed| *speed == 1.)
+                .unwrap_or(0),
+            is_paused: false,
+            is_step_pending: false,
+        }
+    }
+}
+
+impl TimeControl {
+    pub fn speed(&self) -> f32 {
+        TIME_SPEEDS[self.speed]
+    }
+
+    pub fn describe(&self) -> String {
+        if self.is_paused {
+            "paused".into()
+        } else {
+            format!("{}x", self.speed())
+        }
+    }
+
+    fn apply(&self, time: &mut Time<Virtual>) {
+        time.set_relative_speed(self.speed());
+        if self.is_paused {
+            time.pause();
+        } else {
+            time.unpause();
+        }
+    }
+}
+
+pub struct TimeControlPlugin;
+
+impl Plugin for TimeControlPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TimeControl::default())
+            .insert_resource(TimestepMode::Fixed {
+                dt: PHYSICS_TIMESTEP,
+                substeps: 1,
+            })
+            .insert_resource(Time::<Fixed>::from_seconds(PHYSICS_TIMESTEP.into()))
+            // Before the fixed schedule runs, so a step or pause takes effect the same frame.
+            .add_systems(
+                PreUpdate,
+                (
+                    control_time.run_if(game_state::after_push),
+                    release_time.run_if(not(game_state::after_push)),
+                )
+                    .after(InputSystem)
+                    .after(bindings::update_actions),
+            );
+    }
+}
+
+fn control_time(
+    actions: Res<ActionState>,
+    fixed: Res<Time<Fixed>>,
+    mut control: ResMut<TimeControl>,
+    mut time: ResMut<Time<Virtual>>,
+) {
+    if actions.just_pressed(Action::Pause) {
+        control.is_paused = !control.is_paused;
+    }
+    if actions.just_pressed(Action::SlowDown) {
+        control.speed = control.speed.saturating_sub(1);
+    }
+    if actions.just_pressed(Action::SpeedUp) {
+        control.speed = (control.speed + 1).min(TIME_SPEEDS.len() - 1);
+    }
+    if actions.just_pressed(Action::Step) {
+        control.is_paused = true;
+        control.is_step_pending = true;
+    }
+    if !control.is_paused {
+        control.is_step_pending = false;
+    }
+    // A running clock has already moved on this frame, so the step waits for a frame where
+    // time stood still. Then the step is all of that frame's delta, and the fixed schedule
+    // runs exactly one tick.
+    let is_step = control.is_step_pending && time.is_paused();
+    if is_step {
+        control.is_step_pending = false;
+    }
+    control.apply(&mut time);
+    if is_step {
+        time.advance_by(fixed.timestep());
+    }
+}
+
+/// Puts time back to normal once the run is over.
+fn release_time(mut control: ResMut<TimeControl>, mut time: ResMut<Time<Virtual>>) {
+    if *control != TimeControl::default() {
+        *control = TimeControl::default();
+        control.apply(&mut time);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use bevy::time::{TimePlugin, TimeUpdateStrategy};
+
+    use super::*;
+    use crate::bindings::{Binding, Bindings};
+
+    /// Counts the ticks of the fixed schedule.
+    #[derive(Resource, Default)]
+    struct Ticks(usize);
+
+    fn app() -> App {
+        let mut app = App::new();
+        app.add_plugins(TimePlugin)
+            .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(7)))
+            .insert_resource(Time::<Fixed>::from_seconds(PHYSICS_TIMESTEP.into()))
+            .insert_resource(ButtonInput::<KeyCode>::default())
+            .insert_resource(ButtonInput::<MouseButton>::default())
+            .insert_resource(Bindings::default())
+            .insert_resource(ActionState::default())
+            .insert_resource(TimeControl::default())
+            .init_resource::<Ticks>()
+            .add_systems(PreUpdate, (bindings::update_actions, control_time).chain())
+            .add_systems(FixedUpdate, |mut ticks: ResMut<Ticks>| ticks.0 += 1);
+        app
+    }
+
+    /// Runs a frame, pressing the step key first if asked to.
+    fn update(app: &mut App, press_step: bool) {
+        let key = Bindings::default().actions[&Action::Step]
+            .iter()
+            .find_map(|binding| match binding {
+                Binding::Key(key) => Some(*key),
+                _ => None,
+            })
+            .unwrap();
+        let mut keyboard = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        if press_step {
+            keyboard.press(key);
+        }
+        app.update();
+        let mut keyboard = app.world_mut().resource_mut::<ButtonInput<KeyCode>>();
+        keyboard.release(key);
+        keyboard.clear();
+    }
+
+    fn elapsed(app: &App) -> (Duration, usize) {
+        (
+            app.world().resource::<Time<Virtual>>().elapsed(),
+            app.world().resource::<Ticks>().0,
+        )
+    }
+
+    #[test]
+    fn step_advances_by_exactly_one_timestep() {
+        let mut app = app();
+        for _ in 0..10 {
+            update(&mut app, false);
+        }
+        let timestep = Duration::from_secs_f32(PHYSICS_TIMESTEP);
+
+        // Stepping while running stops time first, then steps on the next frame.
+        update(&mut app, true);
+        let (paused_at, ticks) = elapsed(&app);
+        assert!(app.world().resource::<TimeControl>().is_paused);
+        update(&mut app, false);
+        assert_eq!(elapsed(&app), (paused_at + timestep, ticks + 1));
+
+        // Stepping while paused steps the same frame.
+        update(&mut app, true);
+        assert_eq!(elapsed(&app), (paused_at + timestep * 2, ticks + 2));
+
+        // And time stays still until the next step.
+        update(&mut app, false);
+        assert_eq!(elapsed(&app), (paused_at + timestep * 2, ticks + 2));
+    }
+}
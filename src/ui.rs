@@ -1,32 +1,80 @@
 use bevy::prelude::*;
 // use bevy_simple_subsecond_system::hot;
 
-use crate::level::*;
+use crate::{
+    bindings::{Action, Bindings},
+    curve::ControlPoints,
+    domino::{self, ChainProgress, Domino, DominoBudget, DominoMarker, IsAllValid},
+    editor,
+    game_state::GameState,
+    level::*,
+    path_tool::PathTool,
+    pusher::PushStrength,
+    replay::{Playback, Replay},
+    score::{self, RunScore},
+    time_control::TimeControl,
+};
+
+const BANNER_COLOR: Color = Color::srgb(0.5, 1., 1.);
+const BREAK_RING_RADIUS: f32 = 1.5;
+const BREAK_RING_COLOR: Color = Color::srgb(1., 0.2, 0.2);
 
 pub struct UIPlugin;
 
 #[derive(Component)]
-pub struct WinText;
+pub struct InstructionText;
+
+#[derive(Component)]
+pub struct ChainText;
+
+#[derive(Component)]
+pub struct GoalText;
+
+#[derive(Component)]
+pub struct ReplayText;
 
 #[derive(Component)]
-pub struct CompleteText;
+pub struct TimeText;
 
 impl Plugin for UIPlugin {
     fn build(&self, app: &mut App) {
         app.add_systems(
             Startup,
-            (spawn_instructions, spawn_win_text, spawn_complete_text),
+            (spawn_instructions, spawn_chain_text, spawn_time_text),
+        )
+        .add_systems(OnEnter(GameState::LevelIntro), spawn_intro_text)
+        .add_systems(
+            OnEnter(GameState::Won),
+            spawn_win_text.after(score::score_win),
         )
-        .add_systems(Update, (display_win, display_complete));
+        .add_systems(
+            OnEnter(GameState::AllComplete),
+            spawn_complete_text.after(score::score_win),
+        )
+        .add_systems(OnEnter(GameState::Failed), spawn_failed_text)
+        .add_systems(OnEnter(GameState::Editing), spawn_editor_text)
+        .add_systems(OnEnter(GameState::Replaying), spawn_replay_text)
+        .add_systems(
+            Update,
+            (
+                display_instructions
+                    .run_if(resource_changed::<Bindings>.or(state_changed::<GameState>)),
+                display_chains,
+                display_goals,
+                draw_break_rings.run_if(in_state(GameState::Failed)),
+                draw_likely_breaks.run_if(in_state(GameState::Drawing)),
+                display_replay.run_if(in_state(GameState::Replaying)),
+                display_time,
+            ),
+        );
     }
 }
 
 // #[hot]
 fn spawn_instructions(mut commands: Commands) {
     commands.spawn((
-        Text::new(
-            "Left-Click to add dominoes.\n\nRight-Click or Middle Mouse to orbit.\n\nSpace to start physics.\n\nR or Z to undo.\n\nC to clear all.",
-        ),
+        InstructionText,
+        Text::default(),
         Node {
             position_type: PositionType::Absolute,
             top: Val::Px(12.0),
@@ -35,9 +83,8 @@ fn spawn_instructions(mut commands: Commands) {
         },
     ));
     commands.spawn((
-        Text::new(
-            "Create a path to the goal!\n\nBe sure no markers are red\nor the physics won't start!",
-        ),
+        GoalText,
+        Text::default(),
         Node {
             position_type: PositionType::Absolute,
             top: Val::Px(12.0),
@@ -47,58 +94,516 @@ fn spawn_instructions(mut commands: Commands) {
     ));
 }
 
-// #[hot]
-fn spawn_win_text(mut commands: Commands) {
-    commands.spawn((
-        WinText,
-        Text::new("You Win!\n\n(N)ext Level"),
+/// Lists the controls as currently bound, with gamepad buttons in brackets. Only the ones that
+/// do something in the current state are shown, each next to the action it belongs to.
+fn display_instructions(
+    bindings: Res<Bindings>,
+    state: Res<State<GameState>>,
+    mut text: Single<&mut Text, With<InstructionText>>,
+) {
+    let lines = [
+        (
+            Action::PlacePoint,
+            format!("{} to add dominoes.", bindings.describe(Action::PlacePoint)),
+        ),
+        (
+            Action::PlacePoint,
+            "Drag a point to move it,\nclick the path to insert one.".into(),
+        ),
+        (
+            Action::DeletePoint,
+            format!(
+                "{} removes the hovered point.",
+                bindings.describe(Action::DeletePoint)
+            ),
+        ),
+        (
+            Action::Fork,
+            format!(
+                "{} to fork from the hovered point.",
+                bindings.describe(Action::Fork)
+            ),
+        ),
+        (
+            Action::NewChain,
+            format!(
+                "{} to start a new chain.",
+                bindings.describe(Action::NewChain)
+            ),
+        ),
+        (
+            Action::NextChain,
+            format!(
+                "{} / {} to switch chains.",
+                bindings.describe(Action::PreviousChain),
+                bindings.describe(Action::NextChain)
+            ),
+        ),
+        (
+            Action::ChangeKind,
+            format!(
+                "{} changes the dominoes on the\nhovered part of the path.",
+                bindings.describe(Action::ChangeKind)
+            ),
+        ),
+        (
+            Action::ToggleSpacing,
+            format!(
+                "{} switches how closely dominoes\nare spaced on turns.",
+                bindings.describe(Action::ToggleSpacing)
+            ),
+        ),
+        (
+            Action::CycleTool,
+            format!(
+                "{} switches between points, freehand,\nstraight lines and arcs.",
+                bindings.describe(Action::CycleTool)
+            ),
+        ),
+        (
+            Action::CycleStrength,
+            format!(
+                "{} changes how hard the pusher pushes.\nGentler pushes score more.",
+                bindings.describe(Action::CycleStrength)
+            ),
+        ),
+        (
+            Action::Orbit,
+            format!(
+                "{} to orbit, or the right stick.",
+                bindings.describe(Action::Orbit)
+            ),
+        ),
+        (
+            Action::PlacePoint,
+            "The left stick moves the cursor.".into(),
+        ),
+        (
+            Action::Start,
+            format!("{} to start physics.", bindings.describe(Action::Start)),
+        ),
+        (
+            Action::Stop,
+            format!("{} to stop it.", bindings.describe(Action::Stop)),
+        ),
+        (
+            Action::Pause,
+            format!(
+                "{} pauses the physics,\n{} / {} to slow it down or speed it up.",
+                bindings.describe(Action::Pause),
+                bindings.describe(Action::SlowDown),
+                bindings.describe(Action::SpeedUp)
+            ),
+        ),
+        (
+            Action::Step,
+            format!(
+                "{} steps it one tick at a time.",
+                bindings.describe(Action::Step)
+            ),
+        ),
+        (
+            Action::Undo,
+            format!("{} to undo.", bindings.describe(Action::Undo)),
+        ),
+        (
+            Action::Redo,
+            format!("{} to redo.", bindings.describe(Action::Redo)),
+        ),
+        (
+            Action::Clear,
+            format!("{} to clear all.", bindings.describe(Action::Clear)),
+        ),
+        (
+            Action::Replay,
+            format!(
+                "{} to replay the last run,\n{} to save it.",
+                bindings.describe(Action::Replay),
+                bindings.describe(Action::SaveReplay)
+            ),
+        ),
+        (
+            Action::Editor,
+            format!(
+                "{} to open the level editor.",
+                bindings.describe(Action::Editor)
+            ),
+        ),
+        (
+            Action::LevelSelect,
+            format!(
+                "{} for level select.",
+                bindings.describe(Action::LevelSelect)
+            ),
+        ),
+    ];
+    text.0 = lines
+        .into_iter()
+        .filter(|(action, _)| action.states().contains(state.get()))
+        .map(|(_, line)| line)
+        .collect::<Vec<_>>()
+        .join("\n");
+}
+
+/// Large centred text for the outcome of a run.
+fn banner(text: impl Into<String>, state: GameState) -> impl Bundle {
+    (
+        StateScoped(state),
+        Text::new(text),
         TextFont {
             font_size: 100.0,
             ..default()
         },
-        TextColor(Color::srgb(0.5, 1., 1.)),
-        Visibility::Hidden,
+        TextColor(BANNER_COLOR),
         TextLayout::new_with_justify(JustifyText::Center),
         Node {
             top: Val::Percent(15.0),
             justify_self: JustifySelf::Center,
             ..default()
         },
+    )
+}
+
+fn spawn_intro_text(mut commands: Commands, level: Res<Level>, layout: Res<LevelLayout>) {
+    let title = if layout.name.is_empty() {
+        String::from("Free Mode")
+    } else {
+        format!("{}. {}", level.value + 1, layout.name)
+    };
+    commands.spawn(banner(title, GameState::LevelIntro));
+}
+
+// #[hot]
+/// The run's score, with filled and empty stars out of the level's maximum.
+fn score_line(score: &RunScore, layout: &LevelLayout) -> String {
+    let max = score::max_stars(layout);
+    let stars = "*".repeat(score.stars) + &"-".repeat(max.saturating_sub(score.stars));
+    format!("Score {}  {stars}", score.points)
+}
+
+fn spawn_win_text(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+    score: Res<RunScore>,
+    layout: Res<LevelLayout>,
+) {
+    let text = format!(
+        "You Win!\n{}\n\n{} for the next level",
+        score_line(&score, &layout),
+        bindings.primary(Action::NextLevel)
+    );
+    commands.spawn(banner(text, GameState::Won));
+}
+
+fn spawn_complete_text(
+    mut commands: Commands,
+    bindings: Res<Bindings>,
+    score: Res<RunScore>,
+    layout: Res<LevelLayout>,
+) {
+    let text = format!(
+        "You Win!\n{}\nAll Levels Complete!\n\nPress {} to enter Free Mode",
+        score_line(&score, &layout),
+        bindings.primary(Action::NextLevel)
+    );
+    commands.spawn(banner(text, GameState::AllComplete));
+}
+
+fn spawn_failed_text(mut commands: Commands, bindings: Res<Bindings>) {
+    let text = format!(
+        "The chain stopped!\n\n{} to retry",
+        bindings.primary(Action::Stop)
+    );
+    commands.spawn(banner(text, GameState::Failed));
+}
+
+fn spawn_editor_text(mut commands: Commands, bindings: Res<Bindings>) {
+    let lines = [
+        "Level Editor".into(),
+        format!(
+            "{}: Walls  {}: Goal  {}: Pusher",
+            bindings.describe(Action::EditWalls),
+            bindings.describe(Action::EditGoals),
+            bindings.describe(Action::EditPusher)
+        ),
+        format!(
+            "{} to place or drag.",
+            bindings.describe(Action::EditorPlace)
+        ),
+        format!(
+            "{} / {} to rotate wall.",
+            bindings.describe(Action::RotateWallLeft),
+            bindings.describe(Action::RotateWallRight)
+        ),
+        format!(
+            "{} to remove wall or goal.",
+            bindings.describe(Action::EditorDelete)
+        ),
+        format!(
+            "{} to save to\n{}",
+            bindings.describe(Action::SaveLevel),
+            editor::EDITOR_SAVE_PATH
+        ),
+        format!("{} to play.", bindings.describe(Action::Editor)),
+    ];
+    commands.spawn((
+        StateScoped(GameState::Editing),
+        Text::new(lines.join("\n\n")),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        },
     ));
 }
-fn spawn_complete_text(mut commands: Commands) {
+
+fn spawn_replay_text(mut commands: Commands, bindings: Res<Bindings>) {
     commands.spawn((
-        CompleteText,
-        Text::new("You Win!\nAll Levels Complete!\n\nPress N to enter Free Mode"),
-        TextFont {
-            font_size: 100.0,
+        StateScoped(GameState::Replaying),
+        ReplayText,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
             ..default()
         },
-        TextColor(Color::srgb(0.5, 1., 1.)),
-        Visibility::Hidden,
-        TextLayout::new_with_justify(JustifyText::Center),
+    ));
+    let controls = [
+        format!("{} to pause.", bindings.describe(Action::Pause)),
+        format!(
+            "{} / {} to scrub.",
+            bindings.describe(Action::ScrubBack),
+            bindings.describe(Action::ScrubForward)
+        ),
+        format!(
+            "{} / {} for slower or faster.",
+            bindings.describe(Action::SlowDown),
+            bindings.describe(Action::SpeedUp)
+        ),
+        format!("{} to reverse.", bindings.describe(Action::Reverse)),
+        format!(
+            "{} to draw this path instead.",
+            bindings.describe(Action::LoadReplayPath)
+        ),
+        format!("{} to stop.", bindings.describe(Action::Stop)),
+    ];
+    commands.spawn((
+        StateScoped(GameState::Replaying),
+        Text::new(controls.join("\n")),
         Node {
-            top: Val::Percent(15.0),
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            right: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn spawn_time_text(mut commands: Commands) {
+    commands.spawn((
+        TimeText,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            top: Val::Px(12.0),
             justify_self: JustifySelf::Center,
             ..default()
         },
     ));
 }
 
-// #[hot]
-fn display_win(level: Res<Level>, mut vis: Single<&mut Visibility, With<WinText>>) {
-    **vis = if level.is_won && level.value <= 2 {
-        Visibility::Visible
+/// How fast the physics is running, while it runs.
+fn display_time(
+    state: Res<State<GameState>>,
+    control: Res<TimeControl>,
+    mut text: Single<&mut Text, With<TimeText>>,
+) {
+    let value = if state.get().is_after_push() {
+        format!("Time: {}", control.describe())
     } else {
-        Visibility::Hidden
+        String::new()
+    };
+    if text.0 != value {
+        text.0 = value;
     }
 }
 
-// #[hot]
-fn display_complete(level: Res<Level>, mut vis: Single<&mut Visibility, With<CompleteText>>) {
-    **vis = if level.is_won && level.value > 2 {
-        Visibility::Visible
+/// Where the replay is up to, and how it is playing.
+fn display_replay(
+    replay: Res<Replay>,
+    playback: Res<Playback>,
+    mut text: Single<&mut Text, With<ReplayText>>,
+) {
+    let mut value = format!(
+        "Replay {:.1}s / {:.1}s  {}x",
+        playback.seconds,
+        replay.duration(),
+        playback.speed()
+    );
+    if playback.is_reversed {
+        value.push_str("  reversed");
+    }
+    if playback.is_paused {
+        value.push_str("  paused");
+    }
+    if text.0 != value {
+        text.0 = value;
+    }
+}
+
+fn spawn_chain_text(mut commands: Commands) {
+    commands.spawn((
+        ChainText,
+        Text::default(),
+        Node {
+            position_type: PositionType::Absolute,
+            bottom: Val::Px(12.0),
+            left: Val::Px(12.0),
+            ..default()
+        },
+    ));
+}
+
+fn display_chains(
+    state: Res<State<GameState>>,
+    control_points: Res<ControlPoints>,
+    is_all_valid: Res<IsAllValid>,
+    budget: Res<DominoBudget>,
+    progress: Res<ChainProgress>,
+    (tool, strength): (Res<PathTool>, Res<PushStrength>),
+    mut text: Single<&mut Text, With<ChainText>>,
+) {
+    let state = *state.get();
+    if state != GameState::Drawing && !state.is_after_push() {
+        text.0.clear();
+        return;
+    }
+    if state.is_after_push() {
+        let value = control_points
+            .chains
+            .iter()
+            .zip(&progress.chains)
+            .map(|(chain, status)| format!("{}: {}", chain.name, status.describe()))
+            .collect::<Vec<_>>()
+            .join("\n");
+        if text.0 != value {
+            text.0 = value;
+        }
+        return;
+    }
+    let Some(active) = control_points.chains.get(control_points.active) else {
+        return;
+    };
+    let mut value = format!(
+        "Chain: {} ({}/{})",
+        active.name,
+        control_points.active + 1,
+        control_points.chains.len()
+    );
+    match budget.max {
+        Some(max) => value.push_str(&format!("\nDominoes: {} / {max}", budget.used)),
+        None => value.push_str(&format!("\nDominoes: {}", budget.used)),
+    }
+    value.push_str(&format!("\nTool: {}", tool.describe()));
+    value.push_str(&format!("\nSpacing: {}", control_points.spacing.describe()));
+    value.push_str(&format!("\nPush: {}", strength.describe()));
+    if budget.is_over() {
+        value.push_str("\nToo many dominoes, shorten the path");
+    }
+    for chain in &is_all_valid.invalid_chains {
+        if let Some(chain) = control_points.chains.get(*chain) {
+            value.push_str(&format!("\n{} has overlapping dominoes", chain.name));
+        }
+    }
+    for (chain, index) in &is_all_valid.likely_breaks {
+        if let Some(chain) = control_points.chains.get(*chain) {
+            value.push_str(&format!(
+                "\n{} will likely stop at domino {}",
+                chain.name,
+                index + 1
+            ));
+        }
+    }
+    for chain in &is_all_valid.risky_chains {
+        if is_all_valid.likely_breaks.contains_key(chain) {
+            continue;
+        }
+        if let Some(chain) = control_points.chains.get(*chain) {
+            value.push_str(&format!("\n{} has risky turns", chain.name));
+        }
+    }
+    if text.0 != value {
+        text.0 = value;
+    }
+}
+
+fn display_goals(layout: Res<LevelLayout>, mut text: Single<&mut Text, With<GoalText>>) {
+    if !layout.is_changed() {
+        return;
+    }
+    let mut value = if layout.goals.len() > 1 {
+        String::from("Create a path to the goals!")
     } else {
-        Visibility::Hidden
+        String::from("Create a path to the goal!")
+    };
+    for condition in &layout.win {
+        value.push('\n');
+        value.push_str(&condition.describe());
+    }
+    if let Some(max) = layout.max_dominoes {
+        value.push_str(&format!("\nUse at most {max} dominoes."));
+    }
+    if !layout.switches.is_empty() {
+        value.push_str("\nKnock a domino onto a switch\nto set off what it controls.");
+    }
+    value.push_str("\n\nBe sure no markers are red\nor the physics won't start!");
+    value.push_str("\nOrange markers may not be knocked over.");
+    text.0 = value;
+}
+
+/// Rings the domino each chain stopped at, so it is easy to find after a failed run.
+fn draw_break_rings(
+    query: Query<(&Domino, &Transform)>,
+    progress: Res<ChainProgress>,
+    layout: Res<LevelLayout>,
+    time: Res<Time<Real>>,
+    mut gizmos: Gizmos,
+) {
+    let pulse = 1. + 0.15 * (time.elapsed_secs() * 4.).sin();
+    for (domino, transform) in query.iter() {
+        let Some(status) = progress.chains.get(domino.chain) else {
+            continue;
+        };
+        if status.broke_at == Some(domino.index) {
+            let mut position = transform.translation;
+            position.y = layout.surface_at(position.xz()).height + 0.05;
+            gizmos.circle(
+                Isometry3d::new(position, Quat::from_rotation_arc(Vec3::Z, Vec3::Y)),
+                BREAK_RING_RADIUS * pulse,
+                BREAK_RING_COLOR,
+            );
+        }
+    }
+}
+
+/// Rings the marker each chain is predicted to stop at, before the dominoes are pushed.
+fn draw_likely_breaks(
+    query: Query<(&DominoMarker, &Transform)>,
+    is_all_valid: Res<IsAllValid>,
+    layout: Res<LevelLayout>,
+    mut gizmos: Gizmos,
+) {
+    for (marker, transform) in query.iter() {
+        if is_all_valid.likely_breaks.get(&marker.chain) == Some(&marker.index) {
+            let mut position = transform.translation;
+            position.y = layout.surface_at(position.xz()).height + 0.05;
+            gizmos.circle(
+                Isometry3d::new(position, Quat::from_rotation_arc(Vec3::Z, Vec3::Y)),
+                BREAK_RING_RADIUS,
+                domino::RISKY_COLOR,
+            );
+        }
     }
 }
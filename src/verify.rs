@@ -0,0 +1,324 @@
+use std::{fs, path::Path, time::Duration};
+
+use bevy::{
+    prelude::*, render::mesh::MeshPlugin, scene::ScenePlugin, state::app::StatesPlugin,
+    time::TimeUpdateStrategy,
+};
+use bevy_rapier3d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bindings::ActionState,
+    curve::{self, Chain, ControlPoints},
+    domino::{
+        self, ChainProgress, ChainStatus, DominoKind, DominoMarker, DominoPlugin, IsAllValid,
+    },
+    environment::EnvironmentPlugin,
+    game_state::GameState,
+    goal::{GoalPlugin, GoalProgress},
+    level::{self, LevelLayout},
+    level_asset::{LEVEL_INDEX_PATH, LevelDefinition, LevelIndexFile},
+    mechanism::MechanismPlugin,
+    physics_profile::{DominoPhysicsProfile, PHYSICS_PROFILE_PATH, PhysicsProfilePlugin},
+    placement,
+    pusher::{PushStrength, PusherPlugin},
+    score::{self, RunScore},
+    terrain::TerrainPlugin,
+    time_control,
+};
+
+/// Command line flag that verifies the campaign's known solutions instead of opening the game.
+pub const VERIFY_ARG: &str = "--verify";
+const ASSET_DIR: &str = "assets";
+/// Known solutions for the campaign, next to its index so level paths read the same.
+pub const SOLUTIONS_PATH: &str = "levels/campaign.solutions.ron";
+/// Physics step of every verification run, the same as the game's.
+pub const VERIFY_TIMESTEP: f32 = time_control::PHYSICS_TIMESTEP;
+/// Runs that haven't won after this many seconds count as failed.
+pub const VERIFY_TIMEOUT: f32 = 60.;
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct SolutionFile {
+    pub solutions: Vec<SolutionDefinition>,
+}
+
+/// A path that should solve a level.
+/// Control points are given on the floor plane as `(x, z)`, one list per chain,
+/// and stand on whatever ground the level has there.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct SolutionDefinition {
+    /// The level file, relative to the campaign index.
+    pub level: String,
+    /// The first chain starts at the pusher.
+    pub chains: Vec<Vec<Vec2>>,
+    /// The kind of domino along each segment of each chain, standard where left out.
+    #[serde(default)]
+    pub kinds: Vec<Vec<DominoKind>>,
+    #[serde(default)]
+    pub strength: PushStrength,
+}
+
+impl SolutionDefinition {
+    pub fn control_points(&self, level: &LevelDefinition) -> ControlPoints {
+        let chains = self
+            .chains
+            .iter()
+            .enumerate()
+            .map(|(index, points)| Chain {
+                name: if index == 0 {
+                    "Main".into()
+                } else {
+                    format!("Chain {index}")
+                },
+                points: points
+                    .iter()
+                    .map(|point| {
+                        let height = level.surface_at(*point).height;
+                        vec3(point.x, domino::standing_y(height), point.y)
+                    })
+                    .collect(),
+                parent: None,
+                kinds: self.kinds.get(index).cloned().unwrap_or_default(),
+                shapes: Vec::new(),
+            })
+            .collect();
+        ControlPoints {
+            chains,
+            ..default()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VerifyOutcome {
+    /// A marker overlapped something, so the game would refuse to start.
+    Invalid,
+    /// Every win condition held this many seconds after the push.
+    Won {
+        seconds: f32,
+        score: RunScore,
+    },
+    /// Every chain came to rest without winning.
+    Failed,
+    TimedOut,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct VerifyReport {
+    pub outcome: VerifyOutcome,
+    pub dominoes: usize,
+    /// How far each chain fell by the end of the run.
+    pub chains: Vec<ChainStatus>,
+}
+
+/// Plays a path on a level without a window, stepping physics at [`VERIFY_TIMESTEP`].
+/// The same inputs always give the same report, on any platform as long as the
+/// `deterministic` feature is on, as it is by default.
+pub fn verify_solution(
+    level: &LevelDefinition,
+    control_points: ControlPoints,
+    strength: PushStrength,
+) -> VerifyReport {
+    let path_length = curve::path_length(&control_points);
+    let mut app = headless_app(level, control_points);
+    app.insert_resource(strength);
+
+    // Give Rapier a frame to report marker overlaps before they are checked, as in the game.
+    app.update();
+    app.update();
+    let dominoes = app
+        .world_mut()
+        .query_filtered::<(), With<DominoMarker>>()
+        .iter(app.world())
+        .count();
+    if !app.world().resource::<IsAllValid>().value {
+        return VerifyReport {
+            outcome: VerifyOutcome::Invalid,
+            dominoes,
+            chains: Vec::new(),
+        };
+    }
+
+    app.world_mut()
+        .resource_mut::<NextState<GameState>>()
+        .set(GameState::Simulating);
+    let steps = (VERIFY_TIMEOUT / VERIFY_TIMESTEP).ceil() as usize;
+    let mut outcome = VerifyOutcome::TimedOut;
+    for _ in 0..steps {
+        app.update();
+        match app.world().resource::<State<GameState>>().get() {
+            GameState::Won | GameState::AllComplete => {
+                let seconds = app.world().resource::<GoalProgress>().elapsed;
+                let score = score::score_run(level, dominoes, path_length, seconds, strength);
+                outcome = VerifyOutcome::Won { seconds, score };
+                break;
+            }
+            GameState::Failed => {
+                outcome = VerifyOutcome::Failed;
+                break;
+            }
+            _ => {}
+        }
+    }
+    VerifyReport {
+        outcome,
+        dominoes,
+        chains: app.world().resource::<ChainProgress>().chains.clone(),
+    }
+}
+
+/// Checks that every campaign level has a known solution that still wins.
+pub fn verify_campaign() -> AppExit {
+    let index = match read_ron::<LevelIndexFile>(LEVEL_INDEX_PATH) {
+        Ok(index) => index,
+        Err(err) => {
+            eprintln!("Could not read the level index: {err}");
+            return AppExit::error();
+        }
+    };
+    let solutions = match read_ron::<SolutionFile>(SOLUTIONS_PATH) {
+        Ok(file) => file.solutions,
+        Err(err) => {
+            eprintln!("Could not read the known solutions: {err}");
+            return AppExit::error();
+        }
+    };
+    let level_dir = Path::new(LEVEL_INDEX_PATH)
+        .parent()
+        .unwrap_or(Path::new(""));
+
+    let mut failures = 0;
+    for path in &index.levels {
+        let level = match read_ron::<LevelDefinition>(level_dir.join(path)) {
+            Ok(level) => level,
+            Err(err) => {
+                eprintln!("{path}: could not read level: {err}");
+                failures += 1;
+                continue;
+            }
+        };
+        let reports: Vec<VerifyReport> = solutions
+            .iter()
+            .filter(|solution| &solution.level == path)
+            .map(|solution| {
+                verify_solution(&level, solution.control_points(&level), solution.strength)
+            })
+            .collect();
+        let best = reports
+            .iter()
+            .filter_map(|report| match report.outcome {
+                VerifyOutcome::Won { seconds, score } => Some((seconds, report.dominoes, score)),
+                _ => None,
+            })
+            .min_by(|a, b| a.0.total_cmp(&b.0));
+        match best {
+            Some((seconds, dominoes, score)) => {
+                println!(
+                    "{path}: won in {seconds:.2}s with {dominoes} dominoes, scoring {} ({}/{} stars)",
+                    score.points,
+                    score.stars,
+                    score::max_stars(&level)
+                );
+            }
+            None if reports.is_empty() => {
+                eprintln!("{path}: no known solution");
+                failures += 1;
+            }
+            None => {
+                eprintln!("{path}: no solution wins");
+                for report in &reports {
+                    eprintln!("  {:?} with {} dominoes", report.outcome, report.dominoes);
+                    for (chain, status) in report.chains.iter().enumerate() {
+                        eprintln!("    chain {chain}: {}", status.describe());
+                    }
+                }
+                failures += 1;
+            }
+        }
+    }
+
+    if failures == 0 {
+        AppExit::Success
+    } else {
+        eprintln!("{failures} of {} levels failed", index.levels.len());
+        AppExit::error()
+    }
+}
+
+fn headless_app(level: &LevelDefinition, control_points: ControlPoints) -> App {
+    let timestep = Duration::from_secs_f32(VERIFY_TIMESTEP);
+    // Read up front, so a run doesn't depend on when the profile asset finishes loading.
+    let profile = read_ron::<DominoPhysicsProfile>(PHYSICS_PROFILE_PATH).unwrap_or_else(|err| {
+        eprintln!("Using the default physics profile: {err}");
+        default()
+    });
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        TransformPlugin,
+        AssetPlugin::default(),
+        MeshPlugin,
+        ScenePlugin,
+        StatesPlugin,
+    ))
+    .init_asset::<Image>()
+    .init_asset::<StandardMaterial>()
+    .add_plugins(RapierPhysicsPlugin::<NoUserData>::default().in_fixed_schedule())
+    .insert_resource(TimestepMode::Fixed {
+        dt: VERIFY_TIMESTEP,
+        substeps: 1,
+    })
+    .insert_resource(TimeUpdateStrategy::ManualDuration(timestep))
+    .insert_resource(Time::<Fixed>::from_duration(timestep))
+    .init_resource::<ButtonInput<KeyCode>>()
+    .init_resource::<ActionState>()
+    .insert_resource(LevelLayout(level.clone()))
+    .insert_resource(control_points)
+    .insert_resource(profile)
+    .insert_state(GameState::Drawing)
+    .add_plugins((
+        EnvironmentPlugin,
+        DominoPlugin,
+        PhysicsProfilePlugin,
+        PusherPlugin,
+        GoalPlugin,
+        MechanismPlugin,
+        TerrainPlugin,
+    ))
+    .add_systems(Startup, spawn_layout);
+    app.finish();
+    app.cleanup();
+    app
+}
+
+fn spawn_layout(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<StandardMaterial>>,
+    layout: Res<LevelLayout>,
+    control_points: Res<ControlPoints>,
+) {
+    for wall in &layout.walls {
+        level::spawn_wall(&mut commands, &mut meshes, &mut materials, wall);
+    }
+    for (index, chain) in control_points.chains.iter().enumerate() {
+        if let Some(curve) = curve::form_curve(chain) {
+            let placements =
+                placement::place_dominoes(&curve, &chain.kinds, control_points.spacing);
+            curve::spawn_chain_markers(
+                &mut commands,
+                &mut meshes,
+                &mut materials,
+                &placements,
+                index,
+                &layout,
+            );
+        }
+    }
+}
+
+fn read_ron<T: for<'de> Deserialize<'de>>(path: impl AsRef<Path>) -> Result<T, String> {
+    let path = Path::new(ASSET_DIR).join(path);
+    let text = fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+    ron::from_str(&text).map_err(|err| format!("{}: {err}", path.display()))
+}
@@ -0,0 +1,10 @@
+//! Plays the known solution of every campaign level, as `--verify` does, so a change that
+//! makes a level unwinnable fails the tests. Slow, as each level is simulated to the end.
+
+use bevy::app::AppExit;
+use bevy_jam6_game::verify;
+
+#[test]
+fn every_campaign_level_has_a_winning_solution() {
+    assert_eq!(verify::verify_campaign(), AppExit::Success);
+}
@@ -0,0 +1,87 @@
+//! Plays domino chains headless through the same systems as the game, with Rapier stepped on
+//! a fixed timestep, and checks how far they fall. Exact counts are expected, so a change to
+//! domino size, spacing or curve sampling that moves them shows up here first.
+
+use bevy::prelude::*;
+use bevy_jam6_game::{
+    domino::ChainStatus,
+    level_asset::LevelDefinition,
+    pusher::PushStrength,
+    verify::{self, SolutionDefinition, VerifyOutcome, VerifyReport},
+};
+
+const PUSHER: Vec2 = vec2(10., 0.);
+const GOAL: Vec2 = vec2(-10., 0.);
+
+fn level() -> LevelDefinition {
+    LevelDefinition {
+        name: "Test".into(),
+        goals: vec![GOAL],
+        pusher: PUSHER,
+        ..default()
+    }
+}
+
+/// Plays one chain starting at the pusher.
+fn play(level: &LevelDefinition, points: &[Vec2]) -> VerifyReport {
+    let solution = SolutionDefinition {
+        level: String::new(),
+        chains: vec![points.to_vec()],
+        kinds: Vec::new(),
+        strength: PushStrength::Normal,
+    };
+    verify::verify_solution(level, solution.control_points(level), solution.strength)
+}
+
+/// Checks how many dominoes were placed and how many had fallen when the run ended.
+/// A winning run ends as soon as the goal is hit, with the last domino still falling.
+fn assert_fallen(report: &VerifyReport, total: usize, fallen: usize) {
+    let [chain] = report.chains.as_slice() else {
+        panic!("expected one chain, got {:?}", report.chains);
+    };
+    let ChainStatus {
+        total: placed,
+        fallen: knocked,
+        broke_at,
+        ..
+    } = chain.clone();
+    assert_eq!(report.dominoes, total);
+    assert_eq!((placed, knocked, broke_at), (total, fallen, None));
+}
+
+#[test]
+fn straight_chain_reaches_the_goal() {
+    let report = play(&level(), &[PUSHER, vec2(-7., 0.)]);
+    assert!(
+        matches!(report.outcome, VerifyOutcome::Won { .. }),
+        "{report:?}"
+    );
+    assert_fallen(&report, 14, 13);
+}
+
+#[test]
+fn curved_chain_reaches_the_goal() {
+    let level = LevelDefinition {
+        goals: vec![vec2(0., 10.)],
+        ..level()
+    };
+    let report = play(&level, &[PUSHER, vec2(4., 0.), vec2(0., 4.), vec2(0., 7.)]);
+    assert!(
+        matches!(report.outcome, VerifyOutcome::Won { .. }),
+        "{report:?}"
+    );
+    assert_fallen(&report, 12, 11);
+}
+
+#[test]
+fn chain_short_of_the_goal_fails_once_fallen() {
+    let report = play(&level(), &[PUSHER, vec2(0., 0.)]);
+    assert_eq!(report.outcome, VerifyOutcome::Failed);
+    assert_fallen(&report, 8, 8);
+}
+
+#[test]
+fn same_chain_plays_the_same_every_time() {
+    let points = [PUSHER, vec2(4., 2.), vec2(-2., -2.), vec2(-7., 0.)];
+    assert_eq!(play(&level(), &points), play(&level(), &points));
+}
@@ -0,0 +1,87 @@
+//! Checks where dominoes go along a path and how the path is judged, without any physics.
+
+use bevy::prelude::*;
+use bevy_jam6_game::{
+    analysis::{self, Risk},
+    domino::DOMINO_DISTANCE,
+    path_tool::{self, SegmentShape},
+    placement::{self, MarkerSpacing},
+};
+
+#[test]
+fn straight_path_is_evenly_spaced() {
+    let points = [Vec3::ZERO, vec3(10., 0., 0.)];
+    let curve = path_tool::build_curve(&points, &[SegmentShape::Straight]).unwrap();
+    let placements = placement::place_dominoes(&curve, &[], MarkerSpacing::Fixed);
+
+    assert_eq!(placements.len(), (10. / DOMINO_DISTANCE) as usize);
+    for (index, placement) in placements.iter().enumerate() {
+        let distance = DOMINO_DISTANCE * (index + 1) as f32;
+        assert!((placement.distance - distance).abs() < 1e-4);
+        assert!(placement.position.distance(vec3(distance, 0., 0.)) < 1e-3);
+        assert!(placement.tangent.normalize().dot(Vec3::X) > 0.999);
+    }
+}
+
+#[test]
+fn smooth_turn_is_spaced_by_arc_length() {
+    let points = [Vec3::ZERO, vec3(5., 0., 0.), vec3(5., 0., 5.)];
+    let curve = path_tool::build_curve(&points, &[]).unwrap();
+    let placements = placement::place_dominoes(&curve, &[], MarkerSpacing::Fixed);
+
+    assert_eq!(placements.len(), 8);
+    for pair in placements.windows(2) {
+        let step = pair[1].distance - pair[0].distance;
+        assert!((step - DOMINO_DISTANCE).abs() < 1e-4);
+        // Chords on a bend are a little shorter than the distance along it.
+        let chord = pair[0].position.distance(pair[1].position);
+        assert!(
+            chord <= step + 1e-3 && chord > step * 0.95,
+            "{chord} vs {step}"
+        );
+    }
+}
+
+#[test]
+fn adaptive_spacing_holds_a_sharp_turn() {
+    let points = [Vec3::ZERO, vec3(5., 0., 0.), vec3(5., 0., 5.)];
+    let curve = path_tool::build_curve(&points, &[]).unwrap();
+    let place = |spacing| {
+        let placements = placement::place_dominoes(&curve, &[], spacing);
+        let breaks = analysis::analyse_path(&placements)
+            .iter()
+            .filter(|analysis| analysis.risk == Risk::Breaks)
+            .count();
+        (placements.len(), breaks)
+    };
+    assert_eq!(place(MarkerSpacing::Fixed), (8, 1));
+    let (markers, breaks) = place(MarkerSpacing::Adaptive);
+    assert!(markers > 8);
+    assert_eq!(breaks, 0);
+}
+
+#[test]
+fn gap_in_path_is_predicted_to_break() {
+    let points = [Vec3::ZERO, vec3(10., 0., 0.)];
+    let curve = path_tool::build_curve(&points, &[SegmentShape::Straight]).unwrap();
+    let mut placements = placement::place_dominoes(&curve, &[], MarkerSpacing::Fixed);
+    placements.remove(4);
+
+    let risks: Vec<Risk> = analysis::analyse_path(&placements)
+        .iter()
+        .map(|analysis| analysis.risk)
+        .collect();
+    assert_eq!(risks[4], Risk::Breaks);
+    assert_eq!(risks.iter().filter(|risk| **risk != Risk::Safe).count(), 1);
+}
+
+#[test]
+fn straight_stroke_simplifies_to_its_ends() {
+    let stroke: Vec<Vec3> = (0..=20)
+        .map(|step| vec3(step as f32 * 0.25, 0., 0.05 * (step % 2) as f32))
+        .collect();
+    assert_eq!(
+        path_tool::simplify_stroke(&stroke),
+        vec![stroke[0], stroke[20]]
+    );
+}